@@ -0,0 +1,142 @@
+// Descrizione delle immagini del gioco (atlante delle celle e sprite sheet)
+//
+// Le immagini sono facoltative: stanno nella cartella assets/ accanto al
+// gioco, descritte dal file assets/atlas.ron. Se un'immagine manca il front
+// end disegna al suo posto muri a cubo e stickman come prima.
+//
+// - L'atlante è un'unica immagine con le celle (pavimento, muro, acqua...):
+//   per ognuna il file dice il rettangolo da ritagliare e il suo "origin",
+//   il punto dell'immagine da mettere al centro del rombo della cella.
+// - Ogni personaggio ha il suo sprite sheet: una riga per ognuna delle 8
+//   direzioni e una colonna per fotogramma (la prima è il personaggio fermo,
+//   le altre il ciclo della camminata). Qui l'origin è il punto dei piedi.
+//
+// Un pixel dell'immagine è un'unità del mondo: un rombo è largo 64 e alto 32.
+// Questo modulo non carica niente, legge solo la descrizione e calcola quale
+// pezzo di immagine usare: le texture le gestisce il front end.
+//
+// Esempio di assets/atlas.ron:
+//
+// (
+//     image: "tiles.png",
+//     tiles: {
+//         "floor": (rect: (0, 0, 64, 32), origin: (32, 16)),
+//         "wall": (rect: (64, 0, 64, 56), origin: (32, 40)),
+//     },
+//     sheets: {
+//         "player": (image: "player.png", size: (32, 48), frames: 5, fps: 8, origin: (16, 44)),
+//         "Skeleton": (image: "skeleton.png", size: (32, 48), frames: 5, fps: 8, origin: (16, 44)),
+//     },
+// )
+
+use crate::game::Motion;
+use crate::map::Tile;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// Un pezzo dell'atlante
+#[derive(Clone, Debug, Deserialize)]
+pub struct Frame {
+    pub rect: (f32, f32, f32, f32), // x, y, larghezza, altezza nell'immagine
+    pub origin: (f32, f32),         // Punto da mettere al centro della cella
+}
+
+// Sprite sheet di un personaggio: 8 righe (vedi facing) di `frames` fotogrammi
+#[derive(Clone, Debug, Deserialize)]
+pub struct Sheet {
+    pub image: String,      // File dell'immagine (nella cartella assets)
+    pub size: (f32, f32),   // Larghezza e altezza di un fotogramma
+    pub frames: usize,      // Fotogrammi per riga (il primo è da fermo)
+    pub fps: f32,           // Velocità della camminata in fotogrammi al secondo
+    pub origin: (f32, f32), // Punto dei piedi nel fotogramma
+}
+
+impl Sheet {
+    // Rettangolo (x, y, larghezza, altezza) del fotogramma `frame`
+    // nella riga della direzione `dir`
+    pub fn source(&self, dir: usize, frame: usize) -> (f32, f32, f32, f32) {
+        let (w, h) = self.size;
+        (frame as f32 * w, dir as f32 * h, w, h)
+    }
+
+    // Fotogramma da disegnare: il primo se il personaggio è fermo, altrimenti
+    // uno del ciclo di camminata che scorre con il tempo t (in secondi)
+    pub fn frame(&self, moving: bool, t: f32) -> usize {
+        if !moving || self.frames < 2 {
+            return 0;
+        }
+        1 + (t * self.fps) as usize % (self.frames - 1)
+    }
+}
+
+// Contenuto di assets/atlas.ron (ogni campo si può omettere).
+// Le celle e i personaggi senza voce (o senza immagine) restano procedurali
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Atlas {
+    pub image: String,                 // Immagine dell'atlante delle celle
+    pub tiles: HashMap<String, Frame>, // Nome della cella -> pezzo dell'atlante
+    // Nome del personaggio -> sprite sheet: "player", il nome del tipo di
+    // mostro (come in data/monsters.ron) o il ruolo dell'NPC ("Healer", "Merchant")
+    pub sheets: HashMap<String, Sheet>,
+}
+
+impl Atlas {
+    // Legge la descrizione dal testo RON
+    pub fn parse(text: &str) -> Result<Atlas, String> {
+        ron::from_str(text).map_err(|e| e.to_string())
+    }
+}
+
+// Nome nell'atlante di una cella della mappa (il forziere della città è "chest")
+pub fn tile_name(tile: Tile) -> &'static str {
+    match tile {
+        Tile::Floor => "floor",
+        Tile::Wall => "wall",
+        Tile::Rubble => "rubble",
+        Tile::Water => "water",
+        Tile::StairsDown => "stairs_down",
+        Tile::StairsUp => "stairs_up",
+    }
+}
+
+// Riga dello sprite sheet per un passo (dx, dy) sulla griglia.
+// Le righe seguono le direzioni sullo schermo in senso orario partendo dal
+// basso: sud, sud-ovest, ovest, nord-ovest, nord, nord-est, est, sud-est.
+// Nella vista isometrica il passo (1, 1) scende dritto sullo schermo, (1, 0)
+// va in basso a destra e così via. Senza passo il personaggio guarda in basso
+pub fn facing(step: (i32, i32)) -> usize {
+    match (step.0.signum(), step.1.signum()) {
+        (1, 1) => 0,
+        (0, 1) => 1,
+        (-1, 1) => 2,
+        (-1, 0) => 3,
+        (-1, -1) => 4,
+        (0, -1) => 5,
+        (1, -1) => 6,
+        (1, 0) => 7,
+        _ => 0,
+    }
+}
+
+// Direzione (riga dello sprite sheet) e movimento di un personaggio
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Pose {
+    pub dir: usize,
+    pub moving: bool,
+}
+
+impl Pose {
+    // Posa di chi sta facendo (o ha appena finito) il passo `motion` verso `to`:
+    // guarda nella direzione dell'ultimo passo, e cammina finché non è arrivato
+    pub fn new(motion: &Motion, to: (usize, usize)) -> Self {
+        let step = (
+            to.0 as i32 - motion.from.0 as i32,
+            to.1 as i32 - motion.from.1 as i32,
+        );
+        Pose {
+            dir: facing(step),
+            moving: motion.t < 1.,
+        }
+    }
+}
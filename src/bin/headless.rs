@@ -0,0 +1,128 @@
+// Simulatore headless: gioca una partita senza aprire nessuna finestra
+//
+// Uso:
+//   cargo run --bin headless -- [seed] [secondi] [--record file.replay]
+//   cargo run --bin headless -- --replay file.replay
+//
+// Un "bot" molto semplice sostituisce il mouse: quando il player è fermo,
+// gli ordina di andare verso il mostro più vicino che vede (e quindi di
+// attaccarlo); se non ne vede nessuno esplora verso la zona inesplorata più vicina.
+// Con --replay invece riesegue una partita registrata (es. allegata a una
+// segnalazione di bug) e stampa lo stato finale.
+// La simulazione avanza a tick fissi: stesso seed = stesso risultato.
+
+use crablo::dungeon::GenMode;
+use crablo::fov::Visibility;
+use crablo::game::{Command, Game, TICK};
+use crablo::path::dist;
+use crablo::replay::{Playback, Replay};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // Modalità replay: riesegui i comandi registrati
+    if let Some(i) = args.iter().position(|a| a == "--replay") {
+        let Some(file) = args.get(i + 1) else {
+            eprintln!("--replay needs a file");
+            std::process::exit(1);
+        };
+        let replay = match Replay::load(file) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("cannot load {file}: {e}");
+                std::process::exit(1);
+            }
+        };
+        let mut game = replay.start();
+        let mut playback = Playback::new(replay);
+        while !playback.finished(&game) {
+            if playback.step(&mut game) {
+                println!("tick {}: game over", game.tick);
+                break;
+            }
+        }
+        report(&game);
+        return;
+    }
+
+    let record = args
+        .iter()
+        .position(|a| a == "--record")
+        .and_then(|i| args.get(i + 1));
+    let mut numbers = args.iter().filter_map(|a| a.parse::<f64>().ok());
+    let seed = numbers.next().map_or(42, |s| s as u64);
+    let secs = numbers.next().map_or(60., |s| s as f32);
+
+    let mut game = Game::new(seed, GenMode::Rooms, (20, 20));
+    let mut recording = Replay::new(&game);
+    let ticks = (secs / TICK) as u32;
+
+    for _ in 0..ticks {
+        let mut commands = vec![];
+        if game.target.is_none() {
+            let player = (game.px, game.py);
+            if let Some(m) = game
+                .monsters
+                .iter()
+                .filter(|m| game.fog.visible((m.x, m.y)))
+                .min_by_key(|m| dist((m.x, m.y), player))
+            {
+                commands.push(Command::MoveTo((m.x, m.y)));
+            } else if game.path.is_empty()
+                && let Some(cell) = frontier(&game)
+            {
+                commands.push(Command::MoveTo(cell));
+            }
+        }
+
+        recording.record(game.tick, &commands);
+        if game.update(&commands) {
+            println!("tick {}: game over", game.tick);
+            break;
+        }
+        if game.monsters.is_empty() {
+            println!("tick {}: all monsters defeated", game.tick);
+            break;
+        }
+    }
+
+    if let Some(file) = record
+        && let Err(e) = recording.save(file)
+    {
+        eprintln!("cannot save {file}: {e}");
+    }
+    report(&game);
+}
+
+// Cella esplorata e calpestabile più vicina al player che confina con una
+// cella mai vista: andandoci il player scopre un altro pezzo di mappa
+fn frontier(game: &Game) -> Option<(usize, usize)> {
+    let player = (game.px, game.py);
+    let unexplored = |x: usize, y: usize| {
+        game.map.in_bounds(x, y) && game.fog.get((x, y)) == Visibility::Unexplored
+    };
+    game.map
+        .iter()
+        .filter(|&(c, t)| t.walkable() && c != player && game.fog.get(c) != Visibility::Unexplored)
+        .map(|(c, _)| c)
+        .filter(|&(x, y)| {
+            unexplored(x + 1, y)
+                || unexplored(x, y + 1)
+                || (x > 0 && unexplored(x - 1, y))
+                || (y > 0 && unexplored(x, y - 1))
+        })
+        .min_by_key(|&c| dist(c, player))
+}
+
+// Stampa un riassunto dello stato finale della partita
+fn report(game: &Game) {
+    println!(
+        "seed {}: tick {} - player ({}, {}) hp {} - monsters left {}",
+        game.seed,
+        game.tick,
+        game.px,
+        game.py,
+        game.hp,
+        game.monsters.len()
+    );
+}
@@ -0,0 +1,108 @@
+// Tipi di danno, resistenze e risultato dei colpi
+//
+// Ogni colpo ha un tipo (fisico, fuoco, freddo, fulmine, veleno).
+// Il danno fisico subito dal player è ridotto dalla difesa, gli altri dalle
+// resistenze: una resistenza del 40% toglie il 40% del danno di quel tipo.
+// Le resistenze negative sono debolezze e aumentano il danno.
+// Prima del danno un colpo può essere schivato (MISS) o parato (BLOCK),
+// e i colpi del player possono essere critici (CRIT, danno doppio).
+
+use serde::{Deserialize, Serialize};
+
+// Resistenza massima: oltre questa percentuale il danno non cala più
+pub const MAX_RESIST: i32 = 75;
+
+// Moltiplicatore del danno dei colpi critici
+pub const CRIT_MULTIPLIER: f32 = 2.;
+
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum DamageType {
+    #[default]
+    Physical,
+    Fire,
+    Cold,
+    Lightning,
+    Poison,
+}
+
+impl DamageType {
+    pub const ALL: [DamageType; 5] = [
+        DamageType::Physical,
+        DamageType::Fire,
+        DamageType::Cold,
+        DamageType::Lightning,
+        DamageType::Poison,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DamageType::Physical => "physical",
+            DamageType::Fire => "fire",
+            DamageType::Cold => "cold",
+            DamageType::Lightning => "lightning",
+            DamageType::Poison => "poison",
+        }
+    }
+}
+
+// Resistenze in percentuale, una per tipo di danno (nei dati si scrivono
+// solo quelle diverse da 0)
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Resists {
+    pub physical: i32,
+    pub fire: i32,
+    pub cold: i32,
+    pub lightning: i32,
+    pub poison: i32,
+}
+
+impl Resists {
+    pub fn get(&self, kind: DamageType) -> i32 {
+        match kind {
+            DamageType::Physical => self.physical,
+            DamageType::Fire => self.fire,
+            DamageType::Cold => self.cold,
+            DamageType::Lightning => self.lightning,
+            DamageType::Poison => self.poison,
+        }
+    }
+
+    // Danno che rimane dopo la resistenza al suo tipo
+    pub fn reduce(&self, amount: i32, kind: DamageType) -> i32 {
+        let resist = self.get(kind).min(MAX_RESIST);
+        (amount * (100 - resist) / 100).max(0)
+    }
+}
+
+// Com'è andato un colpo (decide il testo fluttuante)
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Outcome {
+    Hit,   // Colpo normale
+    Crit,  // Colpo critico
+    Miss,  // Schivato: nessun danno
+    Block, // Parato: nessun danno
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resists_are_clamped_and_can_be_weaknesses() {
+        let r = Resists {
+            physical: 0,
+            fire: 40,
+            cold: 200,
+            lightning: -50,
+            poison: MAX_RESIST,
+        };
+        assert_eq!(r.reduce(10, DamageType::Physical), 10);
+        assert_eq!(r.reduce(10, DamageType::Fire), 6);
+        // Oltre MAX_RESIST conta come MAX_RESIST: un quarto del danno passa
+        assert_eq!(r.reduce(20, DamageType::Cold), 5);
+        assert_eq!(r.reduce(20, DamageType::Poison), 5);
+        assert_eq!(r.reduce(10, DamageType::Lightning), 15);
+        assert_eq!(r.reduce(0, DamageType::Lightning), 0);
+    }
+}
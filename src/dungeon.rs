@@ -0,0 +1,348 @@
+// Generatore procedurale del dungeon
+//
+// Due modalità:
+// - Rooms: stanze rettangolari collegate da corridoi a "L"
+// - Caves: caverne organiche create con un automa cellulare
+//
+// In entrambi i casi ogni cella calpestabile è raggiungibile dalla posizione di
+// partenza del player: le celle isolate vengono trasformate in muro.
+// Le scale verso il piano inferiore sono nella cella più lontana dalla
+// partenza; quelle verso il piano superiore (se richieste) sulla partenza.
+
+use crate::map::{Grid, Map, Tile};
+use crate::path::{FlowField, dist};
+use crate::rng::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// Distanza minima (Manhattan) tra il player e i mostri allo spawn
+const SPAWN_MIN_DIST: i32 = 6;
+// Dimensione minima della mappa (serve spazio per almeno una stanza e i bordi)
+pub const MIN_SIZE: usize = 9;
+
+// Tipo di generazione della mappa
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GenMode {
+    Rooms, // Stanze e corridoi
+    Caves, // Caverne (automa cellulare)
+}
+
+// Risultato della generazione: mappa, partenza del player e posizioni dei mostri
+// (i mostri vengono piazzati solo su celle Floor)
+pub struct Dungeon {
+    pub map: Map,
+    pub start: (usize, usize),
+    pub spawns: Vec<(usize, usize)>,
+}
+
+// Stanza rettangolare: angolo in alto a sinistra (x, y) e dimensioni (w, h)
+struct Room {
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+}
+
+impl Room {
+    fn center(&self) -> (usize, usize) {
+        (self.x + self.w / 2, self.y + self.h / 2)
+    }
+
+    // Due stanze si sovrappongono se si toccano (lasciamo almeno un muro tra loro)
+    fn intersects(&self, other: &Room) -> bool {
+        self.x <= other.x + other.w
+            && other.x <= self.x + self.w
+            && self.y <= other.y + other.h
+            && other.y <= self.y + self.h
+    }
+}
+
+// Genera un dungeon w x h usando il generatore casuale della partita.
+// Lo stesso seed (con la stessa modalità e dimensione) produce sempre la stessa mappa.
+// up: true se il piano ha anche le scale per salire
+pub fn generate(
+    rng: &mut Rng,
+    mode: GenMode,
+    w: usize,
+    h: usize,
+    monsters: usize,
+    up: bool,
+) -> Dungeon {
+    assert!(
+        w >= MIN_SIZE && h >= MIN_SIZE,
+        "mappa troppo piccola: {w}x{h} (minimo {MIN_SIZE}x{MIN_SIZE})"
+    );
+
+    let (mut map, start) = match mode {
+        GenMode::Rooms => rooms(rng, w, h),
+        GenMode::Caves => caves(rng, w, h),
+    };
+
+    // Garanzia di connettività: ogni Floor non raggiungibile diventa muro
+    let reach = flood(&map, start);
+    for (pos, cell) in map.iter_mut() {
+        if !reach[pos] {
+            *cell = Tile::Wall;
+        }
+    }
+
+    scatter_terrain(&mut map, rng);
+
+    // Scale: i mostri vengono piazzati solo su Floor, quindi mai sulle scale
+    let flow = FlowField::new(&map, start);
+    if let Some(far) = floor_cells(&map, |pos| pos != start)
+        .into_iter()
+        .max_by_key(|&pos| flow.distance(pos))
+    {
+        map[far] = Tile::StairsDown;
+    }
+    if up {
+        map[start] = Tile::StairsUp;
+    }
+
+    let spawns = place_monsters(&map, start, monsters, rng);
+    Dungeon { map, start, spawns }
+}
+
+// Sparge chiazze di terreno lento (macerie e acqua) sul pavimento.
+// Sono celle calpestabili, quindi la connettività della mappa non cambia.
+fn scatter_terrain(map: &mut Map, rng: &mut Rng) {
+    let (w, h) = (map.width(), map.height());
+    for _ in 0..w * h / 80 {
+        let (cx, cy) = (rng.range(1, w - 1), rng.range(1, h - 1));
+        let tile = if rng.chance(0.5) {
+            Tile::Rubble
+        } else {
+            Tile::Water
+        };
+        let r = rng.range(1, 3);
+        for y in cy.saturating_sub(r)..=(cy + r).min(h - 1) {
+            for x in cx.saturating_sub(r)..=(cx + r).min(w - 1) {
+                // Chiazza irregolare: non tutte le celle del quadrato vengono coperte
+                if map[(x, y)] == Tile::Floor && rng.chance(0.6) {
+                    map[(x, y)] = tile;
+                }
+            }
+        }
+    }
+}
+
+// Stanze e corridoi: prova a piazzare stanze casuali che non si sovrappongono,
+// collegando ogni nuova stanza alla precedente con un corridoio a "L".
+fn rooms(rng: &mut Rng, w: usize, h: usize) -> (Map, (usize, usize)) {
+    let mut map = Map::new(w, h, Tile::Wall);
+    let mut rooms: Vec<Room> = vec![];
+
+    // Più la mappa è grande, più tentativi facciamo
+    for _ in 0..(w * h / 12).max(30) {
+        let rw = rng.range(3, 7);
+        let rh = rng.range(3, 7);
+        let room = Room {
+            x: rng.range(1, w - rw - 1),
+            y: rng.range(1, h - rh - 1),
+            w: rw,
+            h: rh,
+        };
+        if rooms.iter().any(|r| r.intersects(&room)) {
+            continue;
+        }
+
+        // Scava la stanza
+        for y in room.y..room.y + room.h {
+            for x in room.x..room.x + room.w {
+                map[(x, y)] = Tile::Floor;
+            }
+        }
+
+        // Collega al centro della stanza precedente
+        if let Some(prev) = rooms.last() {
+            let (ax, ay) = prev.center();
+            let (bx, by) = room.center();
+            // Scegli a caso se andare prima in orizzontale o in verticale
+            if rng.chance(0.5) {
+                carve_h(&mut map, ax, bx, ay);
+                carve_v(&mut map, ay, by, bx);
+            } else {
+                carve_v(&mut map, ay, by, ax);
+                carve_h(&mut map, ax, bx, by);
+            }
+        }
+        rooms.push(room);
+    }
+
+    // Il player parte al centro della prima stanza (sempre presente)
+    (map, rooms[0].center())
+}
+
+// Corridoio orizzontale da x1 a x2 sulla riga y
+fn carve_h(map: &mut Map, x1: usize, x2: usize, y: usize) {
+    for x in x1.min(x2)..=x1.max(x2) {
+        map[(x, y)] = Tile::Floor;
+    }
+}
+
+// Corridoio verticale da y1 a y2 sulla colonna x
+fn carve_v(map: &mut Map, y1: usize, y2: usize, x: usize) {
+    for y in y1.min(y2)..=y1.max(y2) {
+        map[(x, y)] = Tile::Floor;
+    }
+}
+
+// Caverne con automa cellulare:
+// 1. riempi la mappa a caso (~45% muri)
+// 2. "leviga" più volte: una cella diventa muro se ha almeno 5 muri attorno
+// 3. tieni solo la caverna più grande (le altre sono isolate)
+fn caves(rng: &mut Rng, w: usize, h: usize) -> (Map, (usize, usize)) {
+    loop {
+        let mut map = Map::new(w, h, Tile::Wall);
+        for y in 1..h - 1 {
+            for x in 1..w - 1 {
+                if !rng.chance(0.45) {
+                    map[(x, y)] = Tile::Floor;
+                }
+            }
+        }
+
+        for _ in 0..4 {
+            map = smooth(&map);
+        }
+
+        // Trova la regione connessa più grande
+        let mut seen = Grid::new(w, h, false);
+        let mut best: Option<((usize, usize), usize)> = None;
+        for y in 0..h {
+            for x in 0..w {
+                if map[(x, y)] == Tile::Floor && !seen[(x, y)] {
+                    let region = flood(&map, (x, y));
+                    let mut size = 0;
+                    for (pos, _) in region.iter().filter(|(_, r)| **r) {
+                        seen[pos] = true;
+                        size += 1;
+                    }
+                    if best.is_none_or(|(_, s)| size > s) {
+                        best = Some(((x, y), size));
+                    }
+                }
+            }
+        }
+
+        // Caverna troppo piccola (meno di un terzo della mappa): riprova
+        if let Some((cell, size)) = best
+            && size >= (w - 2) * (h - 2) / 3
+        {
+            // Punto di partenza casuale dentro la caverna principale
+            let region = flood(&map, cell);
+            let floors = floor_cells(&map, |pos| region[pos]);
+            let start = floors[rng.range(0, floors.len())];
+            return (map, start);
+        }
+    }
+}
+
+// Un passo dell'automa cellulare: conta i muri nel vicinato 3x3
+// (i bordi della mappa restano sempre muri)
+fn smooth(map: &Map) -> Map {
+    let (w, h) = (map.width(), map.height());
+    let mut out = Map::new(w, h, Tile::Wall);
+    for y in 1..h - 1 {
+        for x in 1..w - 1 {
+            let mut walls = 0;
+            for ny in y - 1..=y + 1 {
+                for nx in x - 1..=x + 1 {
+                    if map[(nx, ny)] == Tile::Wall {
+                        walls += 1;
+                    }
+                }
+            }
+            out[(x, y)] = if walls >= 5 { Tile::Wall } else { Tile::Floor };
+        }
+    }
+    out
+}
+
+// Flood fill (stessa logica a 4 direzioni di bfs): marca tutte le celle Floor
+// raggiungibili da start
+fn flood(map: &Map, start: (usize, usize)) -> Grid<bool> {
+    let mut visited = Grid::new(map.width(), map.height(), false);
+    visited[start] = true;
+    let mut q = VecDeque::from([start]);
+
+    while let Some(curr) = q.pop_front() {
+        for (dx, dy) in [(0, -1), (0, 1), (-1, 0), (1, 0)] {
+            let (nx, ny) = ((curr.0 as i32 + dx) as usize, (curr.1 as i32 + dy) as usize);
+            if map.is_walkable(nx, ny) && !visited[(nx, ny)] {
+                visited[(nx, ny)] = true;
+                q.push_back((nx, ny));
+            }
+        }
+    }
+    visited
+}
+
+// Tutte le celle Floor che soddisfano il filtro
+fn floor_cells(map: &Map, filter: impl Fn((usize, usize)) -> bool) -> Vec<(usize, usize)> {
+    map.iter()
+        .filter(|&(pos, &t)| t == Tile::Floor && filter(pos))
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+// Sceglie le posizioni dei mostri tra le celle Floor libere,
+// preferendo quelle lontane dal player
+fn place_monsters(
+    map: &Map,
+    start: (usize, usize),
+    count: usize,
+    rng: &mut Rng,
+) -> Vec<(usize, usize)> {
+    let mut cells = floor_cells(map, |pos| dist(pos, start) >= SPAWN_MIN_DIST);
+    // Mappa troppo piccola: accontentati di qualsiasi cella diversa dalla partenza
+    if cells.len() < count {
+        cells = floor_cells(map, |pos| pos != start);
+    }
+
+    let mut spawns = vec![];
+    while spawns.len() < count && !cells.is_empty() {
+        spawns.push(cells.swap_remove(rng.range(0, cells.len())));
+    }
+    spawns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::bfs;
+
+    #[test]
+    fn floors_are_connected_with_stairs_and_far_spawns() {
+        for mode in [GenMode::Rooms, GenMode::Caves] {
+            for (w, h) in [(MIN_SIZE, MIN_SIZE), (20, 20), (40, 25)] {
+                for seed in 1..=5 {
+                    let mut rng = Rng::new(seed);
+                    let d = generate(&mut rng, mode, w, h, 5, true);
+                    let map = &d.map;
+                    let at = format!("{w}x{h} seed {seed}");
+
+                    // Ogni cella calpestabile si raggiunge dalla partenza
+                    for (cell, tile) in map.iter() {
+                        if tile.walkable() && cell != d.start {
+                            assert!(!bfs(map, d.start, cell).is_empty(), "{at}: {cell:?}");
+                        }
+                    }
+                    assert_eq!(map[d.start], Tile::StairsUp, "{at}");
+                    assert!(map.iter().any(|(_, &t)| t == Tile::StairsDown), "{at}");
+
+                    // Mostri su Floor, tutti diversi e lontani se c'è abbastanza spazio
+                    let far = floor_cells(map, |pos| dist(pos, d.start) >= SPAWN_MIN_DIST);
+                    for (i, &s) in d.spawns.iter().enumerate() {
+                        assert_eq!(map[s], Tile::Floor, "{at}");
+                        assert!(!d.spawns[..i].contains(&s), "{at}");
+                        if far.len() >= 5 {
+                            assert!(dist(s, d.start) >= SPAWN_MIN_DIST, "{at}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,296 @@
+// Linea di vista e campo visivo sulla griglia
+//
+// line_of_sight serve a capire se da una cella se ne "vede" un'altra, cioè se
+// tra le due non ci sono muri: le esplosioni delle abilità non li attraversano.
+//
+// Fog invece ricorda cosa ha visto il player: il campo visivo viene ricalcolato
+// a ogni suo passo con lo "shadowcasting simmetrico" (vedi field_of_view),
+// e le celle viste almeno una volta restano esplorate sulla mappa.
+
+use crate::map::{Grid, Map, Tile};
+use serde::{Deserialize, Serialize};
+
+// Raggio del campo visivo del player, in celle
+pub const SIGHT_RADIUS: usize = 8;
+
+// Celle attraversate dal segmento da a a b (estremi inclusi), con
+// l'algoritmo di Bresenham: usa solo interi e non salta nessuna cella,
+// avanzando di uno sull'asse più lungo e accumulando l'errore sull'altro.
+pub fn line(a: (usize, usize), b: (usize, usize)) -> Vec<(usize, usize)> {
+    let (mut x, mut y) = (a.0 as i32, a.1 as i32);
+    let (x1, y1) = (b.0 as i32, b.1 as i32);
+    let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+    let (sx, sy) = ((x1 - x).signum(), (y1 - y).signum());
+    let mut err = dx + dy;
+    let mut cells = vec![];
+
+    loop {
+        cells.push((x as usize, y as usize));
+        if (x, y) == (x1, y1) {
+            return cells;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+// true se tra a e b non ci sono muri (gli estremi non contano)
+pub fn line_of_sight(map: &Map, a: (usize, usize), b: (usize, usize)) -> bool {
+    let cells = line(a, b);
+    cells
+        .iter()
+        .skip(1)
+        .take(cells.len().saturating_sub(2))
+        .all(|&(x, y)| map.get(x, y).is_some_and(|&t| t != Tile::Wall))
+}
+
+// Cosa sa il player di una cella
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Visibility {
+    Unexplored, // Mai vista: non si disegna
+    Explored,   // Vista in passato: si disegna scura, senza mostri
+    Visible,    // Nel campo visivo adesso
+}
+
+// Memoria di cosa il player ha visto su un piano
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Fog {
+    pub tiles: Grid<Visibility>,
+}
+
+impl Fog {
+    // Piano appena scoperto: nessuna cella esplorata
+    pub fn new(w: usize, h: usize) -> Self {
+        Fog {
+            tiles: Grid::new(w, h, Visibility::Unexplored),
+        }
+    }
+
+    // Il player è in `origin`: quello che vedeva prima diventa solo esplorato,
+    // poi si segnano come visibili le celle del nuovo campo visivo
+    pub fn update(&mut self, map: &Map, origin: (usize, usize)) {
+        for (_, v) in self.tiles.iter_mut() {
+            if *v == Visibility::Visible {
+                *v = Visibility::Explored;
+            }
+        }
+        for cell in field_of_view(map, origin, SIGHT_RADIUS) {
+            self.tiles[cell] = Visibility::Visible;
+        }
+    }
+
+    pub fn get(&self, cell: (usize, usize)) -> Visibility {
+        self.tiles
+            .get(cell.0, cell.1)
+            .copied()
+            .unwrap_or(Visibility::Unexplored)
+    }
+
+    // true se la cella è nel campo visivo del player.
+    // Il campo visivo è simmetrico: se il player vede un mostro, il mostro
+    // vede il player, quindi vale anche per sapere chi si accorge di lui
+    pub fn visible(&self, cell: (usize, usize)) -> bool {
+        self.get(cell) == Visibility::Visible
+    }
+
+    // La mappa come la conosce il player: le celle mai viste contano come muri.
+    // Il player calcola i percorsi su questa, così non scopre la strada
+    // attraverso il buio (e non ci si può muovere verso una cella mai vista)
+    pub fn known(&self, map: &Map) -> Map {
+        let mut known = map.clone();
+        for (cell, tile) in known.iter_mut() {
+            if self.get(cell) == Visibility::Unexplored {
+                *tile = Tile::Wall;
+            }
+        }
+        known
+    }
+}
+
+// Pendenza di una riga di scansione come frazione esatta num / den
+// (den sempre positivo): con i float gli arrotondamenti a metà cella
+// darebbero risultati diversi a seconda della direzione
+#[derive(Copy, Clone)]
+struct Slope {
+    num: i32,
+    den: i32,
+}
+
+// Riga di scansione di un quadrante, a distanza `depth` dal centro,
+// compresa tra le pendenze start ed end
+#[derive(Copy, Clone)]
+struct Row {
+    depth: i32,
+    start: Slope,
+    end: Slope,
+}
+
+impl Row {
+    // Colonne della riga: da depth * start arrotondato per eccesso (a metà si
+    // va su) a depth * end arrotondato per difetto (a metà si va giù)
+    fn cols(&self) -> std::ops::RangeInclusive<i32> {
+        let (d, s, e) = (self.depth, self.start, self.end);
+        let min = (2 * d * s.num + s.den).div_euclid(2 * s.den);
+        let max = -(-(2 * d * e.num - e.den)).div_euclid(2 * e.den);
+        min..=max
+    }
+
+    // true se il centro della cella `col` sta tra le due pendenze: solo così la
+    // cella è visibile (i muri invece basta toccarli), ed è questo che rende il
+    // campo visivo simmetrico
+    fn symmetric(&self, col: i32) -> bool {
+        col * self.start.den >= self.depth * self.start.num
+            && col * self.end.den <= self.depth * self.end.num
+    }
+
+    fn next(&self) -> Row {
+        Row {
+            depth: self.depth + 1,
+            ..*self
+        }
+    }
+}
+
+// Celle visibili da `origin` entro `radius` celle, con lo shadowcasting
+// simmetrico (Albert Ford, 2021).
+// Lo spazio attorno al centro si divide in 4 quadranti (nord, sud, est, ovest);
+// ognuno si scandisce riga per riga allontanandosi dal centro. Ogni muro getta
+// un'ombra: la riga successiva si restringe (o si divide in due scansioni
+// ricorsive) tra le pendenze che passano per i bordi dei muri.
+// La proprietà importante: se A vede B allora B vede A.
+pub fn field_of_view(map: &Map, origin: (usize, usize), radius: usize) -> Vec<(usize, usize)> {
+    let mut visible = vec![origin];
+    for quadrant in 0..4 {
+        let first = Row {
+            depth: 1,
+            start: Slope { num: -1, den: 1 },
+            end: Slope { num: 1, den: 1 },
+        };
+        scan(map, origin, quadrant, radius as i32, first, &mut visible);
+    }
+    visible.sort();
+    visible.dedup();
+    visible
+}
+
+// Cella della mappa a riga `depth` e colonna `col` del quadrante (None se fuori)
+fn transform(origin: (usize, usize), quadrant: u8, depth: i32, col: i32) -> Option<(usize, usize)> {
+    let (ox, oy) = (origin.0 as i32, origin.1 as i32);
+    let (x, y) = match quadrant {
+        0 => (ox + col, oy - depth), // nord
+        1 => (ox + depth, oy + col), // est
+        2 => (ox + col, oy + depth), // sud
+        _ => (ox - depth, oy + col), // ovest
+    };
+    (x >= 0 && y >= 0).then_some((x as usize, y as usize))
+}
+
+// Scansione ricorsiva di una riga del quadrante e di quelle dietro
+fn scan(
+    map: &Map,
+    origin: (usize, usize),
+    quadrant: u8,
+    radius: i32,
+    mut row: Row,
+    visible: &mut Vec<(usize, usize)>,
+) {
+    if row.depth > radius {
+        return;
+    }
+    // Fuori dalla mappa conta come muro
+    let wall = |col: i32| {
+        transform(origin, quadrant, row.depth, col)
+            .and_then(|(x, y)| map.get(x, y))
+            .is_none_or(|&t| t == Tile::Wall)
+    };
+    let mut prev: Option<bool> = None; // La cella precedente era un muro?
+    for col in row.cols() {
+        let is_wall = wall(col);
+        // Campo visivo circolare invece che quadrato
+        let near = col * col + row.depth * row.depth <= radius * radius + radius;
+        if near
+            && (is_wall || row.symmetric(col))
+            && let Some((x, y)) = transform(origin, quadrant, row.depth, col)
+            && map.in_bounds(x, y)
+        {
+            visible.push((x, y));
+        }
+        let slope = Slope {
+            num: 2 * col - 1,
+            den: 2 * row.depth,
+        };
+        // Fine di un muro: la parte visibile della riga riparte da qui
+        if prev == Some(true) && !is_wall {
+            row.start = slope;
+        }
+        // Inizio di un muro: si scandisce subito la parte libera fin qui
+        if prev == Some(false) && is_wall {
+            let mut next = row.next();
+            next.end = slope;
+            scan(map, origin, quadrant, radius, next, visible);
+        }
+        prev = Some(is_wall);
+    }
+    if prev == Some(false) {
+        scan(map, origin, quadrant, radius, row.next(), visible);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::{self, GenMode};
+    use crate::rng::Rng;
+    use std::collections::HashSet;
+
+    #[test]
+    fn field_of_view_is_symmetric() {
+        for mode in [GenMode::Rooms, GenMode::Caves] {
+            let map = dungeon::generate(&mut Rng::new(5), mode, 30, 20, 0, true).map;
+            let open: Vec<_> = map
+                .iter()
+                .filter(|&(_, &t)| t != Tile::Wall)
+                .map(|(c, _)| c)
+                .collect();
+            let seen: Vec<HashSet<_>> = open
+                .iter()
+                .map(|&c| field_of_view(&map, c, SIGHT_RADIUS).into_iter().collect())
+                .collect();
+            // Tra due celle libere: se A vede B allora B vede A
+            for (i, &a) in open.iter().enumerate() {
+                for (j, &b) in open.iter().enumerate() {
+                    assert_eq!(seen[i].contains(&b), seen[j].contains(&a), "{a:?} {b:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fog_remembers_cells_out_of_view() {
+        let mut map = Map::new(30, 5, Tile::Wall);
+        for x in 1..29 {
+            for y in 1..4 {
+                map[(x, y)] = Tile::Floor;
+            }
+        }
+        let mut fog = Fog::new(30, 5);
+        fog.update(&map, (2, 2));
+        assert!(fog.visible((3, 2)));
+
+        // Il player va all'altro capo: quello che vedeva resta esplorato
+        fog.update(&map, (27, 2));
+        assert_eq!(fog.get((3, 2)), Visibility::Explored);
+        assert_eq!(fog.get((0, 2)), Visibility::Explored);
+        assert!(fog.visible((26, 2)));
+        assert_eq!(fog.get((15, 2)), Visibility::Unexplored);
+        assert_eq!(fog.known(&map)[(15, 2)], Tile::Wall);
+        assert_eq!(fog.known(&map)[(3, 2)], Tile::Floor);
+    }
+}
@@ -0,0 +1,1654 @@
+// Core della simulazione: stato della partita + comandi
+//
+// Qui non c'è nessuna chiamata a macroquad (niente input, niente finestra,
+// niente disegno): Game avanza solo tramite update(comandi), un tick alla volta.
+// Il front end (main.rs) traduce mouse e tastiera in Command e disegna lo stato;
+// un test o un binario headless possono invece generare i comandi da soli.
+//
+// La simulazione è deterministica: tick di durata fissa e un unico generatore
+// casuale con seed. Stesso seed + stessi comandi agli stessi tick = stessa partita.
+
+use crate::damage::{CRIT_MULTIPLIER, DamageType, Outcome};
+use crate::dungeon::{self, GenMode};
+use crate::fov::{Fog, line_of_sight};
+use crate::item::{self, Equipment, GroundItem, Inventory, ItemClass, ItemData, SELL_RATIO};
+use crate::light::{self, Torch};
+use crate::map::{Map, Tile};
+use crate::monster::{self, Behaviour, MonsterKind};
+use crate::path::{FlowField, Moves, STEP, astar, octile, step_cost};
+use crate::player::{Character, Stat};
+use crate::rng::Rng;
+use crate::skill::{self, Anchor, Effect, SkillDamage, SkillDef};
+use crate::status::{Effects, Inflict};
+use crate::town::{self, MAX_STOCK, Npc, Role};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::mem;
+
+// Durata di un tick della simulazione (60 tick al secondo).
+// La simulazione avanza sempre di questo passo fisso, indipendentemente dagli FPS:
+// così i cooldown durano lo stesso numero di tick su qualsiasi PC.
+pub const TICK: f32 = 1. / 60.;
+
+// Comandi che il giocatore può dare alla simulazione
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Command {
+    MoveTo((usize, usize)), // Cammina fino alla cella (x, y); se c'è un mostro lo prende come bersaglio
+    Attack((usize, usize)), // Attacca sul posto verso la cella (x, y), senza muoversi
+    Stop,                   // Smette di attaccare sul posto
+    AddStat(Stat),          // Spende un punto statistica
+    // Sposta nell'inventario l'oggetto che copre la cella `from` con l'angolo in `to`
+    MoveItem {
+        from: (usize, usize),
+        to: (usize, usize),
+    },
+    DropItem((usize, usize)), // Lascia a terra l'oggetto che copre questa cella dell'inventario
+    Equip((usize, usize)),    // Indossa l'oggetto che copre questa cella dell'inventario
+    Unequip(ItemClass),       // Rimette nell'inventario l'oggetto di questo slot
+    // Lancia l'abilità numero `skill` verso la cella `target`
+    Cast {
+        skill: usize,
+        target: (usize, usize),
+    },
+    // Comandi dei dialoghi con gli NPC della città (validi solo col dialogo giusto aperto)
+    Heal,                     // Guaritore: vita e mana al massimo
+    Buy(usize),               // Mercante: compra l'oggetto in vendita con questo indice
+    Sell((usize, usize)),     // Mercante: vende l'oggetto che copre questa cella dell'inventario
+    Store((usize, usize)),    // Forziere: ci mette l'oggetto che copre questa cella dell'inventario
+    Retrieve((usize, usize)), // Forziere: riprende l'oggetto che copre questa cella del forziere
+    CloseDialog,              // Chiude il dialogo
+}
+
+// Animazione del passo tra due celle.
+// La posizione logica cambia subito, mentre quella disegnata scorre da `from`
+// alla nuova cella durante il passo.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Motion {
+    pub from: (usize, usize), // Cella di partenza del passo
+    pub t: f32,               // Avanzamento (0 = appena partito, 1 = arrivato)
+    pub duration: f32,        // Durata del passo in secondi
+}
+
+impl Motion {
+    // Fermo sulla cella pos
+    fn idle(pos: (usize, usize)) -> Self {
+        Motion {
+            from: pos,
+            t: 1.,
+            duration: 1.,
+        }
+    }
+
+    // Nuovo passo che parte da `from` e dura `duration` secondi
+    fn start(from: (usize, usize), duration: f32) -> Self {
+        Motion {
+            from,
+            t: 0.,
+            duration: duration.max(TICK),
+        }
+    }
+
+    // Avanza l'animazione di un tick
+    fn advance(&mut self) {
+        self.t = (self.t + TICK / self.duration).min(1.);
+    }
+
+    // Posizione da disegnare (coordinate griglia con decimali) tra from e to.
+    // alpha (0..1) è la frazione del prossimo tick già trascorsa: il front end
+    // la usa per interpolare quando gli FPS sono più alti dei tick.
+    pub fn lerp(&self, to: (usize, usize), alpha: f32) -> (f32, f32) {
+        let t = (self.t + alpha * TICK / self.duration).min(1.);
+        (
+            self.from.0 as f32 + (to.0 as f32 - self.from.0 as f32) * t,
+            self.from.1 as f32 + (to.1 as f32 - self.from.1 as f32) * t,
+        )
+    }
+}
+
+// Struttura per i mostri nemici
+#[derive(Serialize, Deserialize)]
+pub struct Monster {
+    pub x: usize,         // Posizione X sulla griglia
+    pub y: usize,         // Posizione Y sulla griglia
+    pub kind: usize,      // Indice del tipo in Game::kinds
+    pub hp: i32,          // Punti vita
+    pub cd: f32,          // Cooldown per azioni (attacco/movimento)
+    pub motion: Motion,   // Animazione dell'ultimo passo
+    pub effects: Effects, // Effetti di stato attivi (veleno, rallentamento...)
+    // true quando si è accorto del player (l'ha visto o è stato colpito):
+    // da quel momento lo insegue
+    pub alert: bool,
+}
+
+impl Monster {
+    // Posizione interpolata da disegnare (vedi Motion::lerp)
+    pub fn draw_pos(&self, alpha: f32) -> (f32, f32) {
+        self.motion.lerp((self.x, self.y), alpha)
+    }
+}
+
+// Chi ha lanciato un proiettile
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Source {
+    Monster(usize), // Un mostro a distanza (indice del suo tipo)
+    Player(usize),  // Il player con un'abilità (indice dell'abilità)
+}
+
+// Proiettile tirato da un mostro a distanza o da un'abilità del player.
+// Vola in linea retta verso la cella bersaglio: chi si sposta in tempo
+// lo schiva, e un muro lo ferma.
+#[derive(Serialize, Deserialize)]
+pub struct Projectile {
+    pub pos: (f32, f32),         // Posizione in coordinate griglia (con decimali)
+    pub dir: (f32, f32),         // Direzione (vettore di lunghezza 1)
+    pub speed: f32,              // Celle al secondo
+    pub range: f32,              // Celle che può ancora percorrere prima di sparire
+    pub damage: i32,             // Danno a chi viene colpito
+    pub radius: f32,             // Raggio dell'esplosione (0 = colpisce solo il bersaglio)
+    pub source: Source,          // Chi l'ha lanciato (decide chi colpisce e il colore)
+    pub status: Option<Inflict>, // Effetto di stato applicato a chi viene colpito
+    pub damage_type: DamageType, // Tipo di danno
+}
+
+// Esplosione o onda di un'abilità: solo un effetto visivo che si allarga e svanisce
+#[derive(Serialize, Deserialize)]
+pub struct Blast {
+    pub pos: (f32, f32), // Centro in coordinate griglia
+    pub radius: f32,     // Raggio finale in celle
+    pub skill: usize,    // Abilità che l'ha prodotta (per il colore)
+    pub life: f32,       // Tempo rimanente (da BLAST_TIME a 0)
+}
+
+// Durata in secondi dell'effetto visivo di un'esplosione
+pub const BLAST_TIME: f32 = 0.4;
+
+// Bersaglio degli attacchi del player
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Target {
+    // Mostro bloccato (indice in monsters): il player lo raggiunge e lo
+    // attacca finché non muore o finché non si clicca altrove
+    Monster(usize),
+    // Attacco sul posto verso una cella: colpisce la cella adiacente in
+    // quella direzione finché non arriva Command::Stop
+    Place((usize, usize)),
+    // NPC da raggiungere (indice in npcs): quando è adiacente si apre il dialogo
+    Npc(usize),
+}
+
+// Piano del dungeon lasciato dal player: quando ci torna lo ritrova com'era
+#[derive(Serialize, Deserialize)]
+pub struct Floor {
+    pub map: Map,
+    pub monsters: Vec<Monster>,
+    pub ground: Vec<GroundItem>,
+    pub npcs: Vec<Npc>,
+    pub fog: Fog,
+    pub torches: Vec<Torch>,
+}
+
+// Numero di mostri di un piano: uno in più a ogni piano
+fn monster_count(depth: u32) -> usize {
+    2 + depth as usize
+}
+
+// Valore (vita, danno, esperienza dei mostri) scalato con la profondità:
+// +25% a ogni piano sotto il primo
+pub fn depth_scale(value: i32, depth: u32) -> i32 {
+    value * (3 + depth as i32) / 4
+}
+
+// Crea i mostri sulle celle scelte dal generatore, con un tipo a caso e la
+// vita scalata con la profondità. Il cooldown iniziale casuale evita che
+// agiscano tutti nello stesso tick.
+fn spawn_monsters(
+    kinds: &[MonsterKind],
+    spawns: &[(usize, usize)],
+    depth: u32,
+    rng: &mut Rng,
+) -> Vec<Monster> {
+    spawns
+        .iter()
+        .map(|&(x, y)| {
+            let kind = monster::pick_kind(kinds, rng);
+            Monster {
+                x,
+                y,
+                kind,
+                hp: depth_scale(kinds[kind].max_hp, depth),
+                cd: rng.range(0, 60) as f32 * TICK,
+                motion: Motion::idle((x, y)),
+                effects: Effects::default(),
+                alert: false,
+            }
+        })
+        .collect()
+}
+
+// Un tick di volo di un proiettile: sposta pos di `step` celle lungo dir e
+// ritorna la cella raggiunta (arrotondando), None se è uscito dalla mappa a
+// sinistra o in alto
+fn fly(pos: &mut (f32, f32), dir: (f32, f32), step: f32) -> Option<(usize, usize)> {
+    pos.0 += dir.0 * step;
+    pos.1 += dir.1 * step;
+    let (cx, cy) = (pos.0.round(), pos.1.round());
+    (cx >= 0. && cy >= 0.).then_some((cx as usize, cy as usize))
+}
+
+// Celle che può percorrere il proiettile di un mostro a distanza: una in più
+// della sua portata di attacco
+fn shot_range(kind: &MonsterKind) -> f32 {
+    kind.attack_range as f32 + 1.
+}
+
+// true se un proiettile tirato da `from` verso `to` a `speed` celle al secondo,
+// che può percorrere `range` celle, arriva sulla cella `to` senza toccare muri.
+// Ripete lo stesso volo dei proiettili veri (stessi passi, stessi
+// arrotondamenti, stessa portata): una linea di Bresenham potrebbe passare dove
+// il proiettile sbatte contro un angolo, e un bersaglio in diagonale può stare
+// entro `attack_range` celle ma oltre la portata del proiettile
+fn clear_shot(map: &Map, from: (usize, usize), to: (usize, usize), speed: f32, range: f32) -> bool {
+    let (dx, dy) = (to.0 as f32 - from.0 as f32, to.1 as f32 - from.1 as f32);
+    let len = (dx * dx + dy * dy).sqrt();
+    let step = speed * TICK;
+    if len == 0. || step <= 0. {
+        return false;
+    }
+    let (dir, mut pos, mut range) = ((dx / len, dy / len), (from.0 as f32, from.1 as f32), range);
+    loop {
+        range -= step;
+        match fly(&mut pos, dir, step) {
+            Some(cell) if cell == to => return true,
+            Some((x, y)) if range > 0. && map.get(x, y).is_some_and(|&t| t != Tile::Wall) => {}
+            _ => return false,
+        }
+    }
+}
+
+// Struttura per il testo fluttuante del danno (floating damage text)
+// Mostra "-10" che sale e scompare quando colpisci un mostro
+// (o "CRIT", "MISS", "BLOCK"; il colore dipende dal tipo di danno).
+// La posizione è in coordinate griglia: è il front end a convertirla sullo schermo.
+#[derive(Serialize, Deserialize)]
+pub struct DmgText {
+    pub x: usize,         // Cella X sopra cui appare il testo
+    pub y: usize,         // Cella Y sopra cui appare il testo
+    pub rise: f32,        // Altezza in pixel sopra la cella (sale nel tempo)
+    pub dmg: i32,         // Quantità di danno da mostrare
+    pub kind: DamageType, // Tipo del danno
+    pub outcome: Outcome, // Colpo normale, critico, schivato o parato
+    pub life: f32,        // Tempo rimanente prima che il testo scompaia (in secondi)
+}
+
+// Struttura principale del gioco: contiene tutto lo stato di una partita
+// (Serialize/Deserialize: viene salvata e caricata da save.rs)
+#[derive(Serialize, Deserialize)]
+pub struct Game {
+    pub map: Map,  // Griglia della mappa (Wall o Floor), dimensioni a runtime
+    pub px: usize, // Posizione X del player sulla griglia
+    pub py: usize, // Posizione Y del player sulla griglia
+    // Percorso calcolato da A*: lista di celle da attraversare per raggiungere il target
+    pub path: Vec<(usize, usize)>,
+    // Bersaglio attuale degli attacchi (None = nessuno)
+    pub target: Option<Target>,
+    // Cooldown movimento: tempo rimanente prima del prossimo passo (in secondi)
+    player_cd: f32,
+    // Animazione dell'ultimo passo del player
+    player_motion: Motion,
+    // Lista dei mostri presenti nella mappa
+    pub monsters: Vec<Monster>,
+    // Piano attuale del dungeon (1 = il primo) e piani già visitati,
+    // messi da parte così come il player li ha lasciati
+    pub depth: u32,
+    pub floors: BTreeMap<u32, Floor>,
+    // Personaggi non giocanti del piano (solo in città) e NPC con cui si sta parlando
+    pub npcs: Vec<Npc>,
+    pub dialog: Option<usize>,
+    // Celle del piano esplorate e celle nel campo visivo del player
+    pub fog: Fog,
+    // Torce e bracieri del piano (solo grafica: illuminano la mappa)
+    pub torches: Vec<Torch>,
+    // Oro per comprare dal mercante
+    pub gold: u32,
+    // Forziere della città: una seconda griglia come l'inventario
+    pub stash: Inventory,
+    // Tipi di mostro (da data/monsters.ron). Salvati con la partita, così un
+    // salvataggio resta coerente anche se il file dei dati cambia.
+    pub kinds: Vec<MonsterKind>,
+    // Proiettili in volo
+    pub projectiles: Vec<Projectile>,
+    // Abilità del player (da data/skills.ron) e tempo di ricarica rimanente di ognuna
+    pub skills: Vec<SkillDef>,
+    pub skill_cd: Vec<f32>,
+    // Esplosioni in corso (solo grafica)
+    pub blasts: Vec<Blast>,
+    // Mana rigenerato non ancora arrivato a un punto intero
+    mana_regen: f32,
+    // Oggetti e tabelle di loot (da data/items.ron), salvati come i tipi di mostro
+    pub item_data: ItemData,
+    // Oggetti a terra
+    pub ground: Vec<GroundItem>,
+    // Inventario a griglia del player
+    pub inventory: Inventory,
+    // Oggetti indossati (arma, armatura, elmo, anello, amuleto)
+    pub equipment: Equipment,
+    // Flow field verso il player, condivisa da tutti i mostri.
+    // Ricalcolata solo quando il player cambia cella.
+    // Non viene salvata: si ricalcola dopo il caricamento (vedi restore).
+    #[serde(skip)]
+    flow: FlowField,
+    // Lista dei testi di danno fluttuanti attivi
+    pub texts: Vec<DmgText>,
+    // Punti vita del player (game over quando <= 0)
+    pub hp: i32,
+    // Colpi diretti andati a segno sul player (non contano veleno e fuoco nel
+    // tempo): il front end fa tremare lo schermo quando il numero cresce
+    pub hits: u32,
+    // Mana del player
+    pub mana: i32,
+    // Effetti di stato attivi sul player
+    pub effects: Effects,
+    // Scheda del personaggio: statistiche, livello ed esperienza
+    pub character: Character,
+    // Numero di tick simulati dall'inizio della partita
+    pub tick: u64,
+    // Seed e modalità con cui è stata generata la mappa (servono per i replay)
+    pub seed: u64,
+    pub mode: GenMode,
+    // Unico generatore casuale della partita: tutta la casualità passa da qui
+    rng: Rng,
+}
+
+impl Game {
+    // Crea una nuova partita con mappa, player e mostri inizializzati
+    // seed: determina la mappa generata (stesso seed = stesso dungeon)
+    // mode: stanze e corridoi oppure caverne
+    // size: dimensioni (larghezza, altezza) della mappa
+    pub fn new(seed: u64, mode: GenMode, size: (usize, usize)) -> Self {
+        let mut rng = Rng::new(seed);
+        // Genera il primo piano e ottieni partenza e spawn dei mostri
+        let dungeon = dungeon::generate(&mut rng, mode, size.0, size.1, monster_count(1), true);
+        // Flow field iniziale dei mostri, calcolata dalla posizione di partenza
+        let flow = FlowField::new(&dungeon.map, dungeon.start);
+        let kinds = monster::default_kinds();
+        let character = Character::new();
+        let skills = skill::default_skills();
+        let monsters = spawn_monsters(&kinds, &dungeon.spawns, 1, &mut rng);
+        let torches =
+            light::place_torches(&dungeon.map, &mut rng, light::torch_count(size.0, size.1));
+        let mut fog = Fog::new(size.0, size.1);
+        fog.update(&dungeon.map, dungeon.start);
+
+        Game {
+            map: dungeon.map,
+            px: dungeon.start.0,
+            py: dungeon.start.1,
+            path: vec![],
+            target: None,
+            player_cd: 0.,
+            player_motion: Motion::idle(dungeon.start),
+            flow,
+            monsters,
+            kinds,
+            depth: 1,
+            floors: BTreeMap::new(),
+            npcs: vec![],
+            dialog: None,
+            fog,
+            torches,
+            gold: 50,
+            stash: Inventory::default(),
+            projectiles: vec![],
+            skill_cd: vec![0.; skills.len()],
+            skills,
+            blasts: vec![],
+            mana_regen: 0.,
+            item_data: item::default_items(),
+            ground: vec![],
+            inventory: Inventory::default(),
+            equipment: Equipment::default(),
+            texts: vec![],
+            // Il player inizia con vita e mana al massimo
+            hp: character.max_hp(),
+            hits: 0,
+            mana: character.max_mana(),
+            effects: Effects::default(),
+            character,
+            tick: 0,
+            seed,
+            mode,
+            rng,
+        }
+    }
+
+    // Esegue un comando del giocatore
+    fn apply(&mut self, cmd: Command) {
+        match cmd {
+            Command::MoveTo((tx, ty)) => {
+                // Un click su un mostro lo blocca come bersaglio, uno su un NPC
+                // porta a parlargli; un click altrove annulla il bersaglio
+                // precedente. Muoversi chiude il dialogo aperto.
+                // I mostri fuori dal campo visivo non si possono bloccare
+                self.dialog = None;
+                self.target = self
+                    .monsters
+                    .iter()
+                    .position(|m| (m.x, m.y) == (tx, ty))
+                    .filter(|_| self.fog.visible((tx, ty)))
+                    .map(Target::Monster)
+                    .or_else(|| {
+                        self.npcs
+                            .iter()
+                            .position(|n| (n.x, n.y) == (tx, ty))
+                            .map(Target::Npc)
+                    });
+                // Verifica: dentro i bounds, non è un muro ed è già stata vista
+                let known = self.fog.known(&self.map);
+                if known.is_walkable(tx, ty) {
+                    // Calcola il percorso con A* (8 direzioni, terreni pesati)
+                    // passando solo per le celle esplorate
+                    self.path = astar(&known, (self.px, self.py), (tx, ty), Moves::Eight, octile);
+                }
+            }
+            Command::Attack(cell) => {
+                // Nessuna direzione se la cella è quella del player
+                if cell != (self.px, self.py) {
+                    self.dialog = None;
+                    self.path.clear();
+                    self.target = Some(Target::Place(cell));
+                }
+            }
+            Command::Stop => {
+                if let Some(Target::Place(_)) = self.target {
+                    self.target = None;
+                }
+            }
+            Command::AddStat(stat) => {
+                // Vitalità ed energia alzano anche i valori attuali, non solo i massimi
+                let (hp, mana) = (self.character.max_hp(), self.character.max_mana());
+                if self.character.spend(stat) {
+                    self.hp += self.character.max_hp() - hp;
+                    self.mana += self.character.max_mana() - mana;
+                }
+            }
+            Command::MoveItem { from, to } => {
+                self.inventory.move_item(from, to);
+            }
+            Command::Equip(cell) => {
+                let Some(i) = self.inventory.at(cell) else {
+                    return;
+                };
+                let slot = self.item_data.bases[self.inventory.items[i].item.base]
+                    .class
+                    .slot();
+                let stored = self.inventory.items.remove(i);
+                // L'oggetto già indossato torna nell'inventario, nello spazio
+                // lasciato libero; se non ci sta lo scambio viene annullato
+                if let Some(old) = self.equipment.slots[slot].take() {
+                    let size = self.item_data.size(&old);
+                    if let Err(old) = self.inventory.insert(old, size) {
+                        self.equipment.slots[slot] = Some(old);
+                        self.inventory.items.insert(i, stored);
+                        return;
+                    }
+                }
+                self.equipment.slots[slot] = Some(stored.item);
+                self.refresh_gear();
+            }
+            Command::Unequip(class) => {
+                if let Some(item) = self.equipment.slots[class.slot()].take() {
+                    let size = self.item_data.size(&item);
+                    if let Err(item) = self.inventory.insert(item, size) {
+                        // Inventario pieno: resta indossato
+                        self.equipment.slots[class.slot()] = Some(item);
+                    }
+                    self.refresh_gear();
+                }
+            }
+            Command::Cast { skill, target } => self.cast(skill, target),
+            Command::DropItem(cell) => {
+                if let Some(item) = self.inventory.take(cell) {
+                    self.ground.push(GroundItem {
+                        x: self.px,
+                        y: self.py,
+                        item,
+                    });
+                }
+            }
+            Command::Heal => {
+                if self.talking(Role::Healer) {
+                    self.hp = self.character.max_hp();
+                    self.mana = self.character.max_mana();
+                    self.effects = Effects::default();
+                }
+            }
+            Command::Buy(i) => {
+                let Some(npc) = self.dialog.filter(|_| self.talking(Role::Merchant)) else {
+                    return;
+                };
+                let Some(item) = self.npcs[npc].stock.get(i) else {
+                    return;
+                };
+                let price = self.item_data.price(item);
+                if self.gold >= price
+                    && self
+                        .inventory
+                        .insert(item.clone(), self.item_data.size(item))
+                        .is_ok()
+                {
+                    self.gold -= price;
+                    self.npcs[npc].stock.remove(i);
+                }
+            }
+            Command::Sell(cell) => {
+                let Some(npc) = self.dialog.filter(|_| self.talking(Role::Merchant)) else {
+                    return;
+                };
+                // L'oggetto venduto finisce tra quelli in vendita (si può ricomprare)
+                if let Some(item) = self.inventory.take(cell) {
+                    self.gold += self.item_data.price(&item) / SELL_RATIO;
+                    let stock = &mut self.npcs[npc].stock;
+                    if stock.len() == MAX_STOCK {
+                        stock.remove(0);
+                    }
+                    stock.push(item);
+                }
+            }
+            Command::Store(cell) => {
+                if self.talking(Role::Stash) {
+                    self.inventory.transfer(cell, &mut self.stash);
+                }
+            }
+            Command::Retrieve(cell) => {
+                if self.talking(Role::Stash) {
+                    self.stash.transfer(cell, &mut self.inventory);
+                }
+            }
+            Command::CloseDialog => self.dialog = None,
+        }
+    }
+
+    // true se è aperto il dialogo con un NPC di questo ruolo
+    fn talking(&self, role: Role) -> bool {
+        self.dialog.is_some_and(|i| self.npcs[i].role == role)
+    }
+
+    // Ricalcola i bonus dell'equipaggiamento dopo un cambio di oggetti.
+    // Vita e mana attuali non possono superare i nuovi massimi
+    fn refresh_gear(&mut self) {
+        self.character.gear = self.equipment.bonuses(&self.item_data);
+        self.hp = self.hp.min(self.character.max_hp());
+        self.mana = self.mana.min(self.character.max_mana());
+    }
+
+    // Ricostruisce lo stato derivato che non viene salvato su file
+    pub(crate) fn restore(&mut self) {
+        self.flow = FlowField::new(&self.map, (self.px, self.py));
+    }
+
+    // Posizione interpolata del player da disegnare (vedi Motion::lerp)
+    pub fn player_draw_pos(&self, alpha: f32) -> (f32, f32) {
+        self.player_motion.lerp((self.px, self.py), alpha)
+    }
+
+    // Passo in corso (o appena finito) del player: il front end ne ricava
+    // la direzione in cui guarda e se sta camminando
+    pub fn player_motion(&self) -> &Motion {
+        &self.player_motion
+    }
+
+    // Avanza la simulazione di un tick (TICK secondi)
+    // commands = comandi del giocatore arrivati in questo tick
+    // Ritorna true se il gioco deve terminare (game over)
+    pub fn update(&mut self, commands: &[Command]) -> bool {
+        // Controllo game over: se HP <= 0, la partita finisce
+        if self.hp <= 0 {
+            return true;
+        }
+        let dt = TICK;
+        self.tick += 1;
+
+        // Avanza le animazioni dei passi
+        self.player_motion.advance();
+        for m in &mut self.monsters {
+            m.motion.advance();
+        }
+
+        // Aggiorna animazione testi di danno fluttuanti
+        // retain_mut mantiene solo i testi con life > 0, rimuovendo quelli scaduti
+        self.texts.retain_mut(|t| {
+            t.life -= dt; // Decrementa il tempo di vita
+            t.rise += 20. * dt; // Fa salire il testo verso l'alto
+            t.life > 0. // Ritorna true se il testo deve rimanere
+        });
+
+        // Ricarica delle abilità, rigenerazione del mana ed esplosioni che svaniscono
+        for cd in &mut self.skill_cd {
+            *cd = (*cd - dt).max(0.);
+        }
+        self.mana_regen += self.character.mana_regen() * dt;
+        while self.mana_regen >= 1. {
+            self.mana_regen -= 1.;
+            self.mana = (self.mana + 1).min(self.character.max_mana());
+        }
+        self.blasts.retain_mut(|b| {
+            b.life -= dt;
+            b.life > 0.
+        });
+
+        // Effetti di stato: scadono col tempo, veleno e fuoco fanno danno
+        // (senza numeri fluttuanti: lo mostra il colore del personaggio)
+        self.hp -= self.effects.update(dt);
+        for i in (0..self.monsters.len()).rev() {
+            self.monsters[i].hp -= self.monsters[i].effects.update(dt);
+            if self.monsters[i].hp <= 0 {
+                self.kill_monster(i);
+            }
+        }
+
+        // Proiettili: avanzano e spariscono quando colpiscono il bersaglio
+        // (il player per quelli dei mostri, un mostro per quelli del player),
+        // un muro o finiscono la loro portata
+        let player = (self.px, self.py);
+        let map = &self.map;
+        let monsters: Vec<_> = self.monsters.iter().map(|m| (m.x, m.y)).collect();
+        let mut hits = vec![];
+        let mut explosions = vec![];
+        self.projectiles.retain_mut(|p| {
+            let step = p.speed * dt;
+            p.range -= step;
+            // Cella in cui si trova ora il proiettile
+            let Some(cell) = fly(&mut p.pos, p.dir, step) else {
+                return false;
+            };
+            match p.source {
+                Source::Monster(_) if cell == player => {
+                    hits.push((p.damage, p.damage_type, p.status));
+                    return false;
+                }
+                Source::Player(skill) if monsters.contains(&cell) => {
+                    let center = (cell.0 as f32, cell.1 as f32);
+                    explosions.push((center, p.radius, p.damage, p.damage_type, skill, p.status));
+                    return false;
+                }
+                _ => {}
+            }
+            p.range > 0. && map.get(cell.0, cell.1).is_some_and(|&t| t != Tile::Wall)
+        });
+        for (dmg, kind, status) in hits {
+            self.damage_player(dmg, kind, status);
+        }
+        for (center, radius, dmg, kind, skill, status) in explosions {
+            self.damage_area(center, radius, (dmg, kind), skill, status);
+        }
+
+        // Comandi del giocatore (es. click sinistro → MoveTo verso la cella cliccata)
+        for &cmd in commands {
+            self.apply(cmd);
+        }
+
+        // Bersaglio bloccato: se il mostro si è spostato ricalcola il percorso
+        // verso di lui. Il percorso finisce sulla sua cella, quindi l'ultimo
+        // passo diventa un attacco (vedi sotto); se è già adiacente basta attaccare
+        let player = (self.px, self.py);
+        let mut strike = None;
+        match self.target {
+            Some(Target::Monster(i)) => {
+                let m = (self.monsters[i].x, self.monsters[i].y);
+                if step_cost(&self.map, player, m).is_some() {
+                    strike = Some(m);
+                } else if self.path.last() != Some(&m) {
+                    let known = self.fog.known(&self.map);
+                    self.path = astar(&known, player, m, Moves::Eight, octile);
+                    // Mostro irraggiungibile: il bersaglio viene annullato
+                    if self.path.is_empty() {
+                        self.target = None;
+                    }
+                }
+            }
+            // NPC: appena è adiacente il player si ferma e si apre il dialogo
+            Some(Target::Npc(i)) => {
+                let n = (self.npcs[i].x, self.npcs[i].y);
+                if step_cost(&self.map, player, n).is_some() {
+                    self.dialog = Some(i);
+                    self.target = None;
+                    self.path.clear();
+                } else if self.path.last() != Some(&n) {
+                    let known = self.fog.known(&self.map);
+                    self.path = astar(&known, player, n, Moves::Eight, octile);
+                    if self.path.is_empty() {
+                        self.target = None;
+                    }
+                }
+            }
+            // Attacco sul posto: la cella adiacente nella direzione del bersaglio
+            Some(Target::Place((tx, ty))) => {
+                let toward = |p: usize, t: usize| match t.cmp(&p) {
+                    Ordering::Less => p - 1,
+                    Ordering::Equal => p,
+                    Ordering::Greater => p + 1,
+                };
+                strike = Some((toward(self.px, tx), toward(self.py, ty)));
+            }
+            None => {}
+        }
+
+        // Movimento del player lungo il percorso A*
+        // Usa un cooldown per controllare la velocità (action_time() per un passo dritto
+        // su Floor, 0.15s con la destrezza iniziale; diagonali e terreni lenti come
+        // acqua e macerie richiedono più tempo)
+        // Da stordito il player non fa nulla; rallentato impiega più tempo
+        let slow = self.effects.cooldown();
+        if (!self.path.is_empty() || strike.is_some()) && !self.effects.stunned() {
+            // Decrementa il cooldown della durata di un tick
+            self.player_cd -= dt;
+
+            // Quando il cooldown arriva a 0, è ora di muoversi
+            if let Some(cell) = strike
+                && self.player_cd <= 0.
+            {
+                // Attacco al bersaglio: il player resta fermo e colpisce la cella
+                // (a vuoto se non c'è nessun mostro)
+                self.player_cd = self.character.action_time() * slow;
+                if let Some(i) = self.monsters.iter().position(|m| (m.x, m.y) == cell) {
+                    self.player_attack(i);
+                }
+            } else if self.player_cd <= 0. {
+                // Prossima cella nel percorso
+                let (nx, ny) = self.path[0];
+
+                // Reset del cooldown proporzionale al costo del passo
+                let cost = step_cost(&self.map, (self.px, self.py), (nx, ny)).unwrap_or(STEP);
+                self.player_cd = self.character.action_time() * cost as f32 / STEP as f32 * slow;
+
+                // Logica di combattimento: controlla se c'è un mostro nella prossima cella
+                // iter().position() cerca l'indice del primo mostro che occupa (nx, ny)
+                if let Some(i) = self.monsters.iter().position(|m| m.x == nx && m.y == ny) {
+                    // Mostro trovato! Attacca invece di muoversi
+                    self.player_attack(i);
+                    // Ferma il movimento: se il mostro era il bersaglio bloccato
+                    // i prossimi attacchi arrivano da soli, altrimenti bisogna cliccare di nuovo
+                    self.path.clear();
+                } else if self.npcs.iter().any(|n| (n.x, n.y) == (nx, ny)) {
+                    // Gli NPC non si possono attraversare
+                    self.path.clear();
+                } else {
+                    // Nessun mostro: muovi il player nella cella
+                    self.path.remove(0);
+                    self.player_motion = Motion::start((self.px, self.py), self.player_cd);
+                    self.px = nx;
+                    self.py = ny;
+                    // Il player si è spostato: aggiorna la flow field dei mostri
+                    // e il campo visivo
+                    self.flow = FlowField::new(&self.map, (self.px, self.py));
+                    self.fog.update(&self.map, (self.px, self.py));
+                    self.arrive();
+                }
+            }
+        }
+
+        // Logica dei Mostri
+        //
+        // NOTA SULLE CLOSURE IN RUST:
+        // Una closure è una funzione anonima che può "catturare" variabili dall'ambiente circostante.
+        // Sintassi: |parametri| espressione  oppure  |parametri| { blocco }
+        //
+        // Esempi:
+        //   |x| x * 2           → prende x, ritorna x * 2
+        //   |a, b| a + b        → prende due parametri, ritorna la somma
+        //   |m| (m.x, m.y)      → prende un Monster, ritorna una tupla con le sue coordinate
+        //
+        // Perché si usano:
+        // - Passare logica custom a funzioni come map(), filter(), retain()
+        // - Sono concise: evitano di definire funzioni separate per operazioni semplici
+        // - Possono accedere a variabili locali (es. self.px, self.py nel chain sotto)
+        //
+        // Equivalente JavaScript: (x) => x * 2  oppure  function(x) { return x * 2; }
+        //
+        // Calcola le celle occupate per evitare che i mostri si sovrappongano.
+        // occupied[i] è la posizione del mostro i (seguono quella del player e
+        // quelle degli NPC): la aggiorniamo man mano che i mostri si muovono.
+        // I mostri inseguono solo il player: gli NPC sono soltanto ostacoli.
+        let mut occupied: Vec<_> = self
+            .monsters
+            .iter()
+            .map(|m| (m.x, m.y)) // Closure: trasforma ogni Monster in una tupla (x, y)
+            .chain(std::iter::once((self.px, self.py))) // Aggiungi la posizione del player
+            .chain(self.npcs.iter().map(|n| (n.x, n.y)))
+            .collect();
+
+        // AI dei mostri: ogni mostro agisce quando il suo cooldown raggiunge 0
+        for i in 0..self.monsters.len() {
+            // Decrementa il cooldown del mostro
+            self.monsters[i].cd -= dt;
+
+            // Quando il cooldown arriva a 0, il mostro può agire (se non è stordito)
+            if self.monsters[i].cd <= 0. && !self.monsters[i].effects.stunned() {
+                // Statistiche del suo tipo (attacco, velocità, comportamento)
+                let kind = &self.kinds[self.monsters[i].kind];
+                // Reset cooldown: il mostro agirà di nuovo dopo il suo tempo di attacco
+                // (più lungo se è rallentato)
+                let slow = self.monsters[i].effects.cooldown();
+                self.monsters[i].cd = kind.attack_cd * slow;
+
+                let (mx, my) = (self.monsters[i].x, self.monsters[i].y);
+
+                // Distanza dal player in celle (anche le diagonali contano 1)
+                let cells = mx.abs_diff(self.px).max(my.abs_diff(self.py));
+                // A portata di attacco? Corpo a corpo = il player è raggiungibile
+                // con un solo passo (anche in diagonale, ma senza tagliare gli angoli
+                // dei muri); a distanza serve anche che il proiettile arrivi al
+                // player senza sbattere contro un muro.
+                let in_range = if kind.attack_range <= 1 {
+                    step_cost(&self.map, (mx, my), (self.px, self.py)).is_some()
+                } else {
+                    cells <= kind.attack_range as usize
+                        && clear_shot(
+                            &self.map,
+                            (mx, my),
+                            (self.px, self.py),
+                            kind.projectile_speed,
+                            shot_range(kind),
+                        )
+                };
+                // Un mostro si accorge del player solo se lo vede (il campo visivo
+                // è simmetrico: lo vede se il player vede lui), e le guardie solo
+                // se è anche abbastanza vicino. Una volta in allerta lo insegue.
+                // Chi attacca a distanza vuole restare ad almeno `keep` celle
+                let (notice, keep) = match kind.behaviour {
+                    Behaviour::Chase => (true, 0),
+                    Behaviour::Guard { aggro } => (
+                        self.flow
+                            .distance((mx, my))
+                            .is_some_and(|d| d <= aggro * STEP),
+                        0,
+                    ),
+                    Behaviour::Kite { keep } => (true, keep as usize),
+                };
+                if notice && self.fog.visible((mx, my)) {
+                    self.monsters[i].alert = true;
+                }
+                let awake = self.monsters[i].alert;
+
+                // Dove muoversi (solo in allerta): prima allontanarsi se il player
+                // è troppo vicino, altrimenti avvicinarsi se non è ancora a portata.
+                // Le celle occupate vengono saltate: se la strada migliore è
+                // bloccata da un altro mostro, si prova un'altra cella.
+                let blocked = |c| occupied.contains(&c);
+                let retreat = if awake && cells < keep {
+                    self.flow.step_away(&self.map, (mx, my), blocked)
+                } else {
+                    None
+                };
+                let step = if retreat.is_some() {
+                    retreat
+                } else if awake && !in_range {
+                    // Mostro lontano: INSEGUI il player scendendo lungo la flow field
+                    self.flow.next_step(&self.map, (mx, my), blocked)
+                } else {
+                    None
+                };
+
+                if let Some((nx, ny)) = step {
+                    // Sui terreni lenti il mostro impiega di più a fare il passo successivo
+                    let cost = step_cost(&self.map, (mx, my), (nx, ny)).unwrap_or(STEP);
+                    self.monsters[i].cd = cost as f32 / STEP as f32 / kind.move_speed * slow;
+                    self.monsters[i].motion = Motion::start((mx, my), self.monsters[i].cd);
+                    self.monsters[i].x = nx;
+                    self.monsters[i].y = ny;
+                    occupied[i] = (nx, ny);
+                } else if awake && in_range && kind.attack_range <= 1 {
+                    // Player adiacente: ATTACCA!
+                    let damage = depth_scale(kind.damage, self.depth);
+                    self.damage_player(damage, kind.damage_type, kind.inflict);
+                } else if awake && in_range {
+                    // Player a tiro: lancia un proiettile verso la sua cella
+                    let (dx, dy) = (self.px as f32 - mx as f32, self.py as f32 - my as f32);
+                    let len = (dx * dx + dy * dy).sqrt();
+                    self.projectiles.push(Projectile {
+                        pos: (mx as f32, my as f32),
+                        dir: (dx / len, dy / len),
+                        speed: kind.projectile_speed,
+                        range: shot_range(kind),
+                        damage: depth_scale(kind.damage, self.depth),
+                        radius: 0.,
+                        source: Source::Monster(self.monsters[i].kind),
+                        status: kind.inflict,
+                        damage_type: kind.damage_type,
+                    });
+                }
+            }
+        }
+
+        false
+    }
+
+    // Il player è appena entrato in una cella: raccoglie gli oggetti e,
+    // se si è fermato su una scala, cambia piano (attraversarla lungo un
+    // percorso più lungo non conta)
+    fn arrive(&mut self) {
+        self.pick_up();
+        if !self.path.is_empty() {
+            return;
+        }
+        match self.map[(self.px, self.py)] {
+            Tile::StairsDown => self.change_floor(self.depth + 1),
+            Tile::StairsUp if self.depth > 0 => self.change_floor(self.depth - 1),
+            _ => {}
+        }
+    }
+
+    // Passa al piano `depth` (0 = la città). Quello attuale viene messo da parte
+    // così com'è; quello di destinazione viene ripreso se già visitato, altrimenti generato.
+    // Il player (con vita, mana, inventario ed effetti) arriva sulle scale
+    // che riportano al piano da cui viene.
+    fn change_floor(&mut self, depth: u32) {
+        let floor = match self.floors.remove(&depth) {
+            Some(floor) => floor,
+            None if depth == 0 => {
+                let (w, h) = (self.map.width(), self.map.height());
+                let (map, npcs, torches) = town::build(w, h, &self.item_data, &mut self.rng);
+                Floor {
+                    map,
+                    monsters: vec![],
+                    ground: vec![],
+                    npcs,
+                    fog: Fog::new(w, h),
+                    torches,
+                }
+            }
+            None => {
+                let (w, h) = (self.map.width(), self.map.height());
+                let count = monster_count(depth);
+                let d = dungeon::generate(&mut self.rng, self.mode, w, h, count, true);
+                let monsters = spawn_monsters(&self.kinds, &d.spawns, depth, &mut self.rng);
+                Floor {
+                    monsters,
+                    torches: light::place_torches(&d.map, &mut self.rng, light::torch_count(w, h)),
+                    map: d.map,
+                    ground: vec![],
+                    npcs: vec![],
+                    fog: Fog::new(w, h),
+                }
+            }
+        };
+        let old = Floor {
+            map: mem::replace(&mut self.map, floor.map),
+            monsters: mem::replace(&mut self.monsters, floor.monsters),
+            ground: mem::replace(&mut self.ground, floor.ground),
+            npcs: mem::replace(&mut self.npcs, floor.npcs),
+            fog: mem::replace(&mut self.fog, floor.fog),
+            torches: mem::replace(&mut self.torches, floor.torches),
+        };
+        self.floors.insert(self.depth, old);
+
+        let stairs = if depth > self.depth {
+            Tile::StairsUp
+        } else {
+            Tile::StairsDown
+        };
+        self.depth = depth;
+        let arrival = self
+            .map
+            .iter()
+            .find(|&(_, &t)| t == stairs)
+            .or_else(|| self.map.iter().find(|&(_, &t)| t.walkable()))
+            .map(|(pos, _)| pos)
+            .expect("piano senza celle calpestabili");
+        (self.px, self.py) = arrival;
+        self.player_motion = Motion::idle(arrival);
+        self.path.clear();
+        self.target = None;
+        self.dialog = None;
+        self.projectiles.clear();
+        self.blasts.clear();
+        self.texts.clear();
+        self.flow = FlowField::new(&self.map, arrival);
+        self.fog.update(&self.map, arrival);
+    }
+
+    // Raccoglie gli oggetti sulla cella del player, se c'è posto nell'inventario
+    // (quelli che non ci stanno restano a terra)
+    fn pick_up(&mut self) {
+        let player = (self.px, self.py);
+        let (inventory, data) = (&mut self.inventory, &self.item_data);
+        self.ground.retain(|g| {
+            (g.x, g.y) != player
+                || inventory
+                    .insert(g.item.clone(), data.size(&g.item))
+                    .is_err()
+        });
+    }
+
+    // Lancia l'abilità `idx` verso la cella target, se è carica, c'è abbastanza
+    // mana e il bersaglio è valido. Gli effetti vengono eseguiti in ordine
+    fn cast(&mut self, idx: usize, target: (usize, usize)) {
+        let Some(skill) = self.skills.get(idx).cloned() else {
+            return;
+        };
+        if self.hp <= 0
+            || self.effects.stunned()
+            || self.skill_cd[idx] > 0.
+            || self.mana < skill.mana
+        {
+            return;
+        }
+        let aim = (target.0 as f32, target.1 as f32);
+        let (dx, dy) = (aim.0 - self.px as f32, aim.1 - self.py as f32);
+        let len = (dx * dx + dy * dy).sqrt();
+        if skill.range > 0. && (len > skill.range || len == 0.) {
+            return;
+        }
+        // Le abilità con un bersaglio si lanciano solo su celle che il player vede
+        // (niente fulmini attraverso i muri o teletrasporti nel buio)
+        if skill.range > 0. && !self.fog.visible(target) {
+            return;
+        }
+        // Il teletrasporto ha bisogno di una cella libera (né mostri né NPC)
+        if skill.effects.contains(&Effect::Teleport)
+            && (!self.map.is_walkable(target.0, target.1)
+                || self.monsters.iter().any(|m| (m.x, m.y) == target)
+                || self.npcs.iter().any(|n| (n.x, n.y) == target))
+        {
+            return;
+        }
+
+        self.mana -= skill.mana;
+        self.skill_cd[idx] = skill.cooldown;
+        for effect in &skill.effects {
+            match *effect {
+                Effect::Area { at, radius, damage } => {
+                    let center = match at {
+                        Anchor::Caster => (self.px as f32, self.py as f32),
+                        Anchor::Target => aim,
+                    };
+                    let amount = self.roll_skill_damage(damage);
+                    self.damage_area(center, radius, (amount, damage.kind), idx, damage.status);
+                }
+                Effect::Projectile {
+                    speed,
+                    radius,
+                    damage,
+                } => {
+                    let amount = self.roll_skill_damage(damage);
+                    self.projectiles.push(Projectile {
+                        pos: (self.px as f32, self.py as f32),
+                        dir: (dx / len, dy / len),
+                        speed,
+                        range: skill.range,
+                        damage: amount,
+                        radius,
+                        source: Source::Player(idx),
+                        status: damage.status,
+                        damage_type: damage.kind,
+                    });
+                }
+                Effect::Teleport => {
+                    self.blasts.push(Blast {
+                        pos: (self.px as f32, self.py as f32),
+                        radius: 0.8,
+                        skill: idx,
+                        life: BLAST_TIME,
+                    });
+                    self.px = target.0;
+                    self.py = target.1;
+                    self.player_motion = Motion::idle(target);
+                    self.path.clear();
+                    self.flow = FlowField::new(&self.map, target);
+                    self.fog.update(&self.map, target);
+                    self.arrive();
+                }
+            }
+        }
+    }
+
+    // Tira il danno di un effetto: frazione di un colpo del player + parte fissa
+    fn roll_skill_damage(&mut self, damage: SkillDamage) -> i32 {
+        let (lo, hi) = self.character.damage();
+        let hit = self.rng.range(lo as usize, hi as usize + 1) as f32 * damage.weapon;
+        let flat = self
+            .rng
+            .range(damage.flat.0 as usize, damage.flat.1 as usize + 1);
+        hit.round() as i32 + flat as i32
+    }
+
+    // Danneggia tutti i mostri entro `radius` celle dal centro (applicando
+    // l'eventuale effetto di stato) e mostra l'esplosione.
+    // I muri riparano: chi non è in linea di vista dal centro non viene colpito.
+    // Gli incantesimi non si possono schivare né parare
+    fn damage_area(
+        &mut self,
+        center: (f32, f32),
+        radius: f32,
+        (amount, kind): (i32, DamageType),
+        skill: usize,
+        status: Option<Inflict>,
+    ) {
+        self.blasts.push(Blast {
+            pos: center,
+            radius: radius.max(0.5),
+            skill,
+            life: BLAST_TIME,
+        });
+        let origin = (center.0.round() as usize, center.1.round() as usize);
+        // Dal più alto al più basso: damage_monster rimuove i mostri morti
+        // e gli indici precedenti restano validi
+        for i in (0..self.monsters.len()).rev() {
+            let (mx, my) = (self.monsters[i].x, self.monsters[i].y);
+            let (dx, dy) = (mx as f32 - center.0, my as f32 - center.1);
+            if (dx * dx + dy * dy).sqrt() > radius || !line_of_sight(&self.map, origin, (mx, my)) {
+                continue;
+            }
+            if let Some(s) = status {
+                let resist = &self.kinds[self.monsters[i].kind].resist;
+                self.monsters[i].effects.apply(s.resisted(resist));
+            }
+            if amount > 0 {
+                self.damage_monster(i, amount, kind);
+            }
+        }
+    }
+
+    // Un colpo può essere schivato o parato: ritorna com'è andato
+    // (Hit se né l'una né l'altra cosa)
+    fn avoid(&mut self, dodge: f32, block: f32) -> Outcome {
+        if self.rng.chance(dodge) {
+            Outcome::Miss
+        } else if self.rng.chance(block) {
+            Outcome::Block
+        } else {
+            Outcome::Hit
+        }
+    }
+
+    // Infligge danno al player e mostra il numero sopra di lui.
+    // Il colpo può essere schivato o parato; altrimenti il danno fisico è
+    // ridotto dalla difesa e gli altri tipi dalle resistenze.
+    // status: effetto di stato del colpo (es. le frecce avvelenate)
+    fn damage_player(&mut self, amount: i32, kind: DamageType, status: Option<Inflict>) {
+        let c = &self.character;
+        let outcome = self.avoid(c.dodge_chance(), c.block_chance());
+        let amount = if outcome != Outcome::Hit {
+            0
+        } else if kind == DamageType::Physical {
+            self.character.reduce(amount)
+        } else {
+            self.character.resists().reduce(amount, kind)
+        };
+        if outcome == Outcome::Hit
+            && let Some(s) = status
+        {
+            self.effects.apply(s.resisted(&self.character.resists()));
+        }
+        self.hp -= amount;
+        if amount > 0 {
+            self.hits += 1;
+        }
+        self.texts.push(DmgText {
+            x: self.px,
+            y: self.py,
+            rise: 0.,
+            dmg: amount,
+            kind,
+            outcome,
+            life: 1.,
+        });
+    }
+
+    // Colpo del player al mostro idx: il mostro può schivarlo o pararlo.
+    // Danno casuale nell'intervallo dato da arma e forza (8-12 all'inizio),
+    // tirato dal generatore della partita
+    fn player_attack(&mut self, idx: usize) {
+        let kind = &self.kinds[self.monsters[idx].kind];
+        let outcome = self.avoid(kind.dodge, kind.block);
+        if outcome != Outcome::Hit {
+            self.texts.push(DmgText {
+                x: self.monsters[idx].x,
+                y: self.monsters[idx].y,
+                rise: 40.,
+                dmg: 0,
+                kind: DamageType::Physical,
+                outcome,
+                life: 1.,
+            });
+            return;
+        }
+        let (lo, hi) = self.character.damage();
+        let dmg = self.rng.range(lo as usize, hi as usize + 1) as i32;
+        self.damage_monster(idx, dmg, DamageType::Physical);
+    }
+
+    // Infligge danno a un mostro e gestisce la sua morte
+    // idx: indice del mostro nel vettore monsters
+    // amount: quantità di danno da infliggere (prima di critico e resistenze)
+    // kind: tipo del danno
+    fn damage_monster(&mut self, idx: usize, amount: i32, kind: DamageType) {
+        // Colpo critico: danno moltiplicato
+        let crit = self.rng.chance(self.character.crit_chance());
+        let (amount, outcome) = if crit {
+            (
+                (amount as f32 * CRIT_MULTIPLIER).round() as i32,
+                Outcome::Crit,
+            )
+        } else {
+            (amount, Outcome::Hit)
+        };
+        // Sottrai HP al mostro (meno la sua resistenza al tipo di danno)
+        let amount = self.kinds[self.monsters[idx].kind]
+            .resist
+            .reduce(amount, kind);
+        self.monsters[idx].hp -= amount;
+        // Chi viene colpito si accorge del player anche se non lo vede
+        self.monsters[idx].alert = true;
+
+        // Crea il testo fluttuante del danno sopra il mostro
+        self.texts.push(DmgText {
+            x: self.monsters[idx].x,
+            y: self.monsters[idx].y,
+            rise: 40., // Parte sopra la testa del mostro
+            dmg: amount,
+            kind,
+            outcome,
+            life: 1., // Dura 1 secondo
+        });
+
+        if self.monsters[idx].hp <= 0 {
+            self.kill_monster(idx);
+        }
+    }
+
+    // Il mostro idx è morto: rimuovilo dal vettore, tira sulla sua tabella
+    // di loot e dai al player l'esperienza del suo tipo
+    fn kill_monster(&mut self, idx: usize) {
+        let dead = self.monsters.remove(idx);
+        // Gli indici dei mostri successivi scalano di uno: il bersaglio
+        // bloccato va aggiornato (o annullato se era proprio questo mostro)
+        if let Some(Target::Monster(t)) = self.target {
+            self.target = match t.cmp(&idx) {
+                Ordering::Less => Some(Target::Monster(t)),
+                Ordering::Equal => None,
+                Ordering::Greater => Some(Target::Monster(t - 1)),
+            };
+        }
+        let table = &self.kinds[dead.kind].loot;
+        if let Some(item) = self.item_data.roll(table, &mut self.rng) {
+            self.ground.push(GroundItem {
+                x: dead.x,
+                y: dead.y,
+                item,
+            });
+        }
+        let xp = depth_scale(self.kinds[dead.kind].xp as i32, self.depth) as u32;
+        // Oro: fino a metà dell'esperienza del mostro
+        self.gold += self.rng.range(0, xp as usize / 2 + 1) as u32;
+        if self.character.gain_xp(xp) > 0 {
+            // Level up: vita e mana tornano al massimo
+            self.hp = self.character.max_hp();
+            self.mana = self.character.max_mana();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fov::Visibility;
+    use crate::path::dist;
+
+    // Partita di prova senza mostri: il player è solo sulla mappa
+    fn empty_game(seed: u64) -> Game {
+        let mut game = Game::new(seed, GenMode::Rooms, (30, 30));
+        game.monsters.clear();
+        game
+    }
+
+    // Avanza fino a `ticks` tick senza comandi, fermandosi quando done è vera
+    fn run_until(game: &mut Game, ticks: u32, done: impl Fn(&Game) -> bool) {
+        for _ in 0..ticks {
+            if done(game) {
+                return;
+            }
+            game.update(&[]);
+        }
+    }
+
+    #[test]
+    fn move_to_reaches_the_cell() {
+        for seed in 1..=5 {
+            let mut game = empty_game(seed);
+            let start = (game.px, game.py);
+            // Una cella di pavimento già in vista, ad almeno qualche passo
+            let goal = game
+                .map
+                .iter()
+                .filter(|&(c, &t)| t == Tile::Floor && game.fog.visible(c))
+                .map(|(c, _)| c)
+                .find(|&c| astar(&game.map, start, c, Moves::Eight, octile).len() >= 3)
+                .expect("nessuna cella lontana in vista");
+
+            game.update(&[Command::MoveTo(goal)]);
+            run_until(&mut game, 600, |g| (g.px, g.py) == goal);
+            assert_eq!((game.px, game.py), goal, "seed {seed}");
+            assert!(game.path.is_empty());
+        }
+    }
+
+    #[test]
+    fn move_to_ignores_unexplored_cells() {
+        let mut game = empty_game(2);
+        let dark = game
+            .map
+            .iter()
+            .find(|&(c, t)| t.walkable() && game.fog.get(c) == Visibility::Unexplored)
+            .map(|(c, _)| c)
+            .expect("tutta la mappa è già esplorata");
+        game.update(&[Command::MoveTo(dark)]);
+        assert!(game.path.is_empty());
+    }
+
+    #[test]
+    fn targeted_skills_need_a_visible_free_cell() {
+        let lightning = 3;
+        let teleport = 4;
+        let mut checked = 0;
+        for seed in 1..=10 {
+            let mut game = empty_game(seed);
+            let player = (game.px, game.py);
+            let near = |c: (usize, usize)| dist(c, player) <= 5 && c != player;
+            // Una cella vicina ma nascosta dietro un muro
+            let Some(hidden) = game
+                .map
+                .iter()
+                .find(|&(c, t)| t.walkable() && near(c) && !game.fog.visible(c))
+                .map(|(c, _)| c)
+            else {
+                continue;
+            };
+            let mana = game.mana;
+            game.update(&[Command::Cast {
+                skill: lightning,
+                target: hidden,
+            }]);
+            game.update(&[Command::Cast {
+                skill: teleport,
+                target: hidden,
+            }]);
+            assert_eq!(game.mana, mana, "seed {seed}");
+            assert_eq!((game.px, game.py), player, "seed {seed}");
+            checked += 1;
+        }
+        assert!(checked > 0);
+
+        // Niente teletrasporto sulla cella di un NPC
+        let mut game = empty_game(1);
+        let player = (game.px, game.py);
+        let cell = game
+            .map
+            .iter()
+            .find(|&(c, t)| t.walkable() && dist(c, player) == 2 && game.fog.visible(c))
+            .map(|(c, _)| c)
+            .unwrap();
+        let (_, mut npcs, _) = town::build(30, 30, &game.item_data, &mut Rng::new(1));
+        npcs.truncate(1);
+        (npcs[0].x, npcs[0].y) = cell;
+        game.npcs = npcs;
+        game.update(&[Command::Cast {
+            skill: teleport,
+            target: cell,
+        }]);
+        assert_eq!((game.px, game.py), player);
+    }
+
+    // Mette il player sulla scala `stairs` del piano e lo fa arrivare lì
+    fn take_stairs(game: &mut Game, stairs: Tile) {
+        let (cell, _) = game.map.iter().find(|&(_, &t)| t == stairs).unwrap();
+        (game.px, game.py) = cell;
+        game.path.clear();
+        game.arrive();
+    }
+
+    #[test]
+    fn floors_keep_their_state_across_stairs() {
+        let mut game = Game::new(2, GenMode::Rooms, (30, 30));
+        let monsters = |g: &Game| {
+            g.monsters
+                .iter()
+                .map(|m| (m.x, m.y, m.kind, m.hp))
+                .collect::<Vec<_>>()
+        };
+        let item = game.item_data.generate(0, &mut Rng::new(1));
+        game.ground.push(GroundItem {
+            x: game.px,
+            y: game.py + 1,
+            item: item.clone(),
+        });
+        game.monsters[0].hp -= 1;
+        game.hp -= 7;
+        game.character.xp = 42;
+        game.inventory.insert(item, (1, 1)).unwrap();
+        let before = monsters(&game);
+        let explored: Vec<_> = game
+            .fog
+            .tiles
+            .iter()
+            .filter(|&(_, &v)| v != Visibility::Unexplored)
+            .map(|(c, _)| c)
+            .collect();
+        let (hp, mana, items) = (game.hp, game.mana, game.inventory.items.len());
+
+        take_stairs(&mut game, Tile::StairsDown);
+        assert_eq!(game.depth, 2);
+        assert_ne!(monsters(&game), before);
+        take_stairs(&mut game, Tile::StairsUp);
+        assert_eq!(game.depth, 1);
+        assert!(game.floors.contains_key(&2));
+
+        // Il piano 1 è come l'avevamo lasciato, e il player non ha perso niente
+        assert_eq!(monsters(&game), before);
+        assert_eq!(game.ground.len(), 1);
+        assert!(
+            explored
+                .iter()
+                .all(|&c| game.fog.get(c) != Visibility::Unexplored)
+        );
+        assert_eq!(game.map[(game.px, game.py)], Tile::StairsDown);
+        assert_eq!((game.hp, game.mana), (hp, mana));
+        assert_eq!(game.inventory.items.len(), items);
+        assert_eq!(game.character.xp, 42);
+    }
+
+    #[test]
+    fn only_direct_hits_count_as_hits() {
+        let mut game = empty_game(4);
+        game.effects.apply(Inflict {
+            kind: crate::status::StatusKind::Poison,
+            power: 5,
+            time: 2.,
+        });
+        let hp = game.hp;
+        run_until(&mut game, 120, |_| false);
+        assert!(game.hp < hp);
+        assert_eq!(game.hits, 0);
+
+        // Un colpo può essere schivato o parato: si riprova finché non va a segno
+        while game.hits == 0 {
+            game.damage_player(50, DamageType::Physical, None);
+        }
+        assert_eq!(game.hits, 1);
+    }
+
+    #[test]
+    fn clear_shots_reach_the_player() {
+        let mut shots = 0;
+        for seed in 1..=5 {
+            let game = empty_game(seed);
+            let player = (game.px, game.py);
+            for (kind, k) in game.kinds.iter().enumerate() {
+                if k.attack_range <= 1 {
+                    continue;
+                }
+                let (speed, range) = (k.projectile_speed, shot_range(k));
+                for (from, _) in game
+                    .map
+                    .iter()
+                    .filter(|&(c, t)| t.walkable() && c != player)
+                {
+                    if !clear_shot(&game.map, from, player, speed, range) {
+                        continue;
+                    }
+                    // Un proiettile vero tirato da lì, come lo tira il mostro,
+                    // deve arrivare al player
+                    let mut g = empty_game(seed);
+                    let (dx, dy) = (
+                        player.0 as f32 - from.0 as f32,
+                        player.1 as f32 - from.1 as f32,
+                    );
+                    let len = (dx * dx + dy * dy).sqrt();
+                    g.projectiles.push(Projectile {
+                        pos: (from.0 as f32, from.1 as f32),
+                        dir: (dx / len, dy / len),
+                        speed,
+                        range,
+                        damage: 1,
+                        radius: 0.,
+                        source: Source::Monster(kind),
+                        status: None,
+                        damage_type: DamageType::Physical,
+                    });
+                    run_until(&mut g, 600, |g| g.projectiles.is_empty());
+                    assert!(!g.texts.is_empty(), "seed {seed} {}: {from:?}", k.name);
+                    shots += 1;
+                }
+            }
+        }
+        assert!(shots > 0);
+    }
+
+    // Partita su una mappa aperta 30x30 (muri solo sul bordo) con il player in (10, 10)
+    fn open_game() -> Game {
+        let mut game = empty_game(1);
+        let mut map = Map::new(30, 30, Tile::Floor);
+        for i in 0..30 {
+            map[(i, 0)] = Tile::Wall;
+            map[(i, 29)] = Tile::Wall;
+            map[(0, i)] = Tile::Wall;
+            map[(29, i)] = Tile::Wall;
+        }
+        game.map = map;
+        game.fog = Fog::new(30, 30);
+        game.torches.clear();
+        (game.px, game.py) = (10, 10);
+        game.player_motion = Motion::idle((10, 10));
+        game.fog.update(&game.map, (10, 10));
+        game.restore();
+        game
+    }
+
+    // Aggiunge un mostro del tipo `name` già in allerta nella cella `cell`
+    fn add_monster(game: &mut Game, name: &str, cell: (usize, usize)) {
+        let kind = game.kinds.iter().position(|k| k.name == name).unwrap();
+        let mut m = spawn_monsters(&game.kinds, &[cell], 1, &mut game.rng).remove(0);
+        m.kind = kind;
+        m.hp = game.kinds[kind].max_hp;
+        m.alert = true;
+        game.monsters.push(m);
+    }
+
+    #[test]
+    fn hits_can_be_dodged_blocked_or_crit() {
+        let mut game = empty_game(1);
+        assert_eq!(game.avoid(1., 0.), Outcome::Miss);
+        assert_eq!(game.avoid(0., 1.), Outcome::Block);
+        assert_eq!(game.avoid(0., 0.), Outcome::Hit);
+
+        // Con lo stesso seed escono gli stessi esiti, nella proporzione giusta
+        let outcomes = |seed| {
+            let mut game = empty_game(seed);
+            (0..2000).map(|_| game.avoid(0.2, 0.5)).collect::<Vec<_>>()
+        };
+        let all = outcomes(9);
+        assert_eq!(all, outcomes(9));
+        let count = |o| all.iter().filter(|&&x| x == o).count();
+        assert!((300..500).contains(&count(Outcome::Miss)));
+        assert!((700..900).contains(&count(Outcome::Block)));
+
+        // Critico sicuro: danno doppio, poi ridotto dalla resistenza del mostro
+        let mut game = open_game();
+        game.character.gear.crit = 100;
+        add_monster(&mut game, "Skeleton", (12, 10));
+        game.monsters[0].hp = 1000;
+        game.damage_monster(0, 10, DamageType::Fire);
+        let resist = &game.kinds[game.monsters[0].kind].resist;
+        let text = game.texts.last().unwrap();
+        assert_eq!(text.outcome, Outcome::Crit);
+        assert_eq!(text.dmg, resist.reduce(20, DamageType::Fire));
+        assert_eq!(game.monsters[0].hp, 1000 - text.dmg);
+
+        // Il player senza destrezza né parata subisce il colpo, ridotto dalla
+        // resistenza (al massimo MAX_RESIST)
+        game.character.dexterity = 0;
+        game.character.gear.fire_resist = 100;
+        let hp = game.hp;
+        game.damage_player(20, DamageType::Fire, None);
+        assert_eq!(game.texts.last().unwrap().outcome, Outcome::Hit);
+        assert_eq!(game.hp, hp - 5);
+    }
+
+    #[test]
+    fn kiting_monsters_retreat_only_when_alerted() {
+        // Arciere a due celle dal player, nascosto dietro un muro
+        let mut game = open_game();
+        for y in 8..=12 {
+            game.map[(11, y)] = Tile::Wall;
+        }
+        game.fog.update(&game.map, (10, 10));
+        add_monster(&mut game, "Archer", (12, 10));
+        game.monsters[0].alert = false;
+        assert!(!game.fog.visible((12, 10)));
+        run_until(&mut game, 120, |_| false);
+        assert_eq!((game.monsters[0].x, game.monsters[0].y), (12, 10));
+
+        // In allerta invece si allontana
+        game.monsters[0].alert = true;
+        run_until(&mut game, 180, |g| {
+            (g.monsters[0].x, g.monsters[0].y) != (12, 10)
+        });
+        assert_ne!((game.monsters[0].x, game.monsters[0].y), (12, 10));
+    }
+
+    #[test]
+    fn archers_do_not_waste_shots_out_of_reach() {
+        // (5, 5) è entro 5 celle in diagonale, ma più lontano della portata
+        // delle frecce: l'arciere deve avvicinarsi e colpire
+        for offset in [(5, 5), (5, 4), (4, 4)] {
+            let mut game = open_game();
+            add_monster(&mut game, "Archer", (10 + offset.0, 10 + offset.1));
+            run_until(&mut game, 600, |g| g.hits > 0);
+            assert!(game.hits > 0, "offset {offset:?}");
+        }
+    }
+
+    #[test]
+    fn attack_hurts_an_adjacent_monster() {
+        let mut game = empty_game(7);
+        // Un mostro fermo in una cella adiacente raggiungibile con un passo
+        let player = (game.px, game.py);
+        let cell = game
+            .map
+            .iter()
+            .map(|(c, _)| c)
+            .find(|&c| c != player && step_cost(&game.map, player, c).is_some())
+            .expect("nessuna cella adiacente libera");
+        let mut monster = spawn_monsters(&game.kinds, &[cell], 1, &mut game.rng).remove(0);
+        // Cooldown lunghissimo: non si muove e non attacca durante il test
+        monster.cd = 1000.;
+        let hp = monster.hp;
+        game.monsters.push(monster);
+
+        game.update(&[Command::Attack(cell)]);
+        run_until(&mut game, 600, |g| {
+            g.monsters.first().is_none_or(|m| m.hp < hp)
+        });
+        assert!(game.monsters.first().is_none_or(|m| m.hp < hp));
+    }
+}
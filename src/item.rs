@@ -0,0 +1,688 @@
+// Oggetti: tipi base, tabelle di loot e inventario a griglia
+//
+// Come per i mostri, i dati stanno in un file (data/items.ron) incluso nel
+// binario: l'elenco degli oggetti con la loro dimensione e le tabelle di loot
+// che decidono cosa lascia cadere un mostro quando muore.
+//
+// L'inventario è una griglia alla Diablo: ogni oggetto occupa un rettangolo
+// di celle (es. una spada 1x3, un'armatura 2x3) e non si può sovrapporre agli altri.
+//
+// Ogni oggetto generato ha una rarità: normale (solo il tipo base), magico
+// (1-2 affissi), raro (2-4 affissi e un nome casuale) o unico (bonus fissi
+// definiti a mano). Gli affissi sono prefissi ("Sharp Short Sword") e suffissi
+// ("Short Sword of the Bear") che aggiungono bonus; anche loro stanno nel file dei dati.
+
+use crate::rng::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Contenuto di data/items.ron
+const ITEMS_RON: &str = include_str!("../data/items.ron");
+
+// Il mercante compra gli oggetti a un quarto del loro prezzo
+pub const SELL_RATIO: u32 = 4;
+
+// Dimensioni della griglia dell'inventario (colonne, righe)
+pub const INV_SIZE: (usize, usize) = (10, 4);
+
+// Categoria dell'oggetto: ognuna ha il suo slot nell'equipaggiamento
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ItemClass {
+    Weapon,
+    Armor,
+    Helm,
+    Ring,
+    Amulet,
+}
+
+impl ItemClass {
+    pub const ALL: [ItemClass; 5] = [
+        ItemClass::Weapon,
+        ItemClass::Armor,
+        ItemClass::Helm,
+        ItemClass::Ring,
+        ItemClass::Amulet,
+    ];
+
+    // Indice dello slot in Equipment
+    pub fn slot(self) -> usize {
+        self as usize
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ItemClass::Weapon => "weapon",
+            ItemClass::Armor => "armor",
+            ItemClass::Helm => "helm",
+            ItemClass::Ring => "ring",
+            ItemClass::Amulet => "amulet",
+        }
+    }
+}
+
+// Tipo base di un oggetto (es. "Short Sword")
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemBase {
+    pub name: String,
+    pub class: ItemClass,
+    pub size: (usize, usize), // Celle occupate nell'inventario (larghezza, altezza)
+    pub color: (f32, f32, f32), // Colore RGB (0..1) usato dal front end
+    #[serde(default)]
+    pub damage: Option<(i32, i32)>, // Danno minimo e massimo (solo armi)
+    #[serde(default)]
+    pub defence: i32, // Difesa (armature ed elmi)
+}
+
+// Rarità di un oggetto
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Rarity {
+    Normal,
+    Magic,
+    Rare,
+    Unique,
+}
+
+// Probabilità delle rarità quando un oggetto viene generato
+// (si prova dalla più rara: unique, poi rare, poi magic, altrimenti normal)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RarityChances {
+    pub magic: f32,
+    pub rare: f32,
+    pub unique: f32,
+}
+
+// Valori che un oggetto può modificare
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Bonus {
+    Damage,      // Danno aggiunto a ogni colpo
+    Defence,     // Difesa
+    MaxHp,       // Punti vita massimi
+    MaxMana,     // Mana massimo
+    AttackSpeed, // Velocità di attacco e movimento in percentuale
+    Strength,
+    Dexterity,
+    Vitality,
+    Energy,
+    FireResist,      // Resistenza al fuoco in percentuale
+    ColdResist,      // Resistenza al freddo in percentuale
+    LightningResist, // Resistenza ai fulmini in percentuale
+    PoisonResist,    // Resistenza al veleno in percentuale
+    Block,           // Probabilità di parare un colpo in percentuale
+    Crit,            // Probabilità di colpo critico in percentuale
+}
+
+impl Bonus {
+    pub const ALL: [Bonus; 15] = [
+        Bonus::Damage,
+        Bonus::Defence,
+        Bonus::MaxHp,
+        Bonus::MaxMana,
+        Bonus::AttackSpeed,
+        Bonus::Strength,
+        Bonus::Dexterity,
+        Bonus::Vitality,
+        Bonus::Energy,
+        Bonus::FireResist,
+        Bonus::ColdResist,
+        Bonus::LightningResist,
+        Bonus::PoisonResist,
+        Bonus::Block,
+        Bonus::Crit,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Bonus::Damage => "damage",
+            Bonus::Defence => "defence",
+            Bonus::MaxHp => "max hp",
+            Bonus::MaxMana => "max mana",
+            Bonus::AttackSpeed => "% attack speed",
+            Bonus::Strength => "strength",
+            Bonus::Dexterity => "dexterity",
+            Bonus::Vitality => "vitality",
+            Bonus::Energy => "energy",
+            Bonus::FireResist => "% fire resist",
+            Bonus::ColdResist => "% cold resist",
+            Bonus::LightningResist => "% lightning resist",
+            Bonus::PoisonResist => "% poison resist",
+            Bonus::Block => "% block",
+            Bonus::Crit => "% crit chance",
+        }
+    }
+}
+
+// Somma dei bonus di uno o più oggetti
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Bonuses {
+    pub weapon: Option<(i32, i32)>, // Danno base dell'arma impugnata
+    pub damage: i32,
+    pub defence: i32,
+    pub max_hp: i32,
+    pub max_mana: i32,
+    pub attack_speed: i32,
+    pub strength: i32,
+    pub dexterity: i32,
+    pub vitality: i32,
+    pub energy: i32,
+    pub fire_resist: i32,
+    pub cold_resist: i32,
+    pub lightning_resist: i32,
+    pub poison_resist: i32,
+    pub block: i32,
+    pub crit: i32,
+}
+
+impl Bonuses {
+    // Riferimento al campo che corrisponde al bonus
+    fn field(&mut self, bonus: Bonus) -> &mut i32 {
+        match bonus {
+            Bonus::Damage => &mut self.damage,
+            Bonus::Defence => &mut self.defence,
+            Bonus::MaxHp => &mut self.max_hp,
+            Bonus::MaxMana => &mut self.max_mana,
+            Bonus::AttackSpeed => &mut self.attack_speed,
+            Bonus::Strength => &mut self.strength,
+            Bonus::Dexterity => &mut self.dexterity,
+            Bonus::Vitality => &mut self.vitality,
+            Bonus::Energy => &mut self.energy,
+            Bonus::FireResist => &mut self.fire_resist,
+            Bonus::ColdResist => &mut self.cold_resist,
+            Bonus::LightningResist => &mut self.lightning_resist,
+            Bonus::PoisonResist => &mut self.poison_resist,
+            Bonus::Block => &mut self.block,
+            Bonus::Crit => &mut self.crit,
+        }
+    }
+
+    pub fn add(&mut self, bonus: Bonus, value: i32) {
+        *self.field(bonus) += value;
+    }
+
+    pub fn get(&self, bonus: Bonus) -> i32 {
+        match bonus {
+            Bonus::Damage => self.damage,
+            Bonus::Defence => self.defence,
+            Bonus::MaxHp => self.max_hp,
+            Bonus::MaxMana => self.max_mana,
+            Bonus::AttackSpeed => self.attack_speed,
+            Bonus::Strength => self.strength,
+            Bonus::Dexterity => self.dexterity,
+            Bonus::Vitality => self.vitality,
+            Bonus::Energy => self.energy,
+            Bonus::FireResist => self.fire_resist,
+            Bonus::ColdResist => self.cold_resist,
+            Bonus::LightningResist => self.lightning_resist,
+            Bonus::PoisonResist => self.poison_resist,
+            Bonus::Block => self.block,
+            Bonus::Crit => self.crit,
+        }
+    }
+}
+
+// Prefisso o suffisso
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum AffixKind {
+    Prefix,
+    Suffix,
+}
+
+// Un affisso: aggiunge `bonus` con un valore casuale tra min e max,
+// solo sulle categorie di oggetto elencate
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Affix {
+    pub name: String,
+    pub kind: AffixKind,
+    pub bonus: Bonus,
+    pub min: i32,
+    pub max: i32,
+    pub classes: Vec<ItemClass>,
+}
+
+// Oggetto unico: nome e bonus fissi su un tipo base
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UniqueItem {
+    pub name: String,
+    pub base: String,
+    pub mods: Vec<(Bonus, i32)>,
+}
+
+// Tabella di loot: probabilità di lasciar cadere qualcosa e, in quel caso,
+// quale oggetto (per nome, con un peso relativo)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LootTable {
+    pub chance: f32,
+    pub drops: Vec<(String, u32)>,
+}
+
+// Tutti i dati sugli oggetti (il contenuto di data/items.ron)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemData {
+    pub bases: Vec<ItemBase>,
+    pub rarity: RarityChances,
+    pub affixes: Vec<Affix>,
+    // Due liste di parole: il nome di un oggetto raro è una parola della prima
+    // seguita da una della seconda (es. "Grim Bite")
+    pub rare_names: (Vec<String>, Vec<String>),
+    pub uniques: Vec<UniqueItem>,
+    pub loot: BTreeMap<String, LootTable>,
+}
+
+impl ItemData {
+    // Indice del tipo base con questo nome
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.bases.iter().position(|b| b.name == name)
+    }
+
+    // Tira sulla tabella di loot indicata: None se non cade nulla
+    // (o se la tabella non esiste, es. mostri senza loot)
+    pub fn roll(&self, table: &str, rng: &mut Rng) -> Option<Item> {
+        let table = self.loot.get(table)?;
+        if !rng.chance(table.chance) {
+            return None;
+        }
+        let total: u32 = table.drops.iter().map(|(_, w)| w).sum();
+        let mut roll = rng.range(0, total as usize) as u32;
+        for (name, weight) in &table.drops {
+            if roll < *weight {
+                return self.find(name).map(|base| self.generate(base, rng));
+            }
+            roll -= weight;
+        }
+        None
+    }
+
+    // Crea un oggetto del tipo base indicato tirando rarità e affissi
+    pub fn generate(&self, base: usize, rng: &mut Rng) -> Item {
+        let b = &self.bases[base];
+        let uniques: Vec<&UniqueItem> = self.uniques.iter().filter(|u| u.base == b.name).collect();
+
+        if !uniques.is_empty() && rng.chance(self.rarity.unique) {
+            let u = uniques[rng.range(0, uniques.len())];
+            return Item {
+                base,
+                rarity: Rarity::Unique,
+                name: u.name.clone(),
+                mods: u.mods.clone(),
+            };
+        }
+
+        // Numero di prefissi e suffissi secondo la rarità
+        let (rarity, prefixes, suffixes) = if rng.chance(self.rarity.rare) {
+            (Rarity::Rare, rng.range(1, 3), rng.range(1, 3))
+        } else if rng.chance(self.rarity.magic) {
+            // Un prefisso, un suffisso o entrambi
+            match rng.range(0, 3) {
+                0 => (Rarity::Magic, 1, 0),
+                1 => (Rarity::Magic, 0, 1),
+                _ => (Rarity::Magic, 1, 1),
+            }
+        } else {
+            (Rarity::Normal, 0, 0)
+        };
+
+        let mut chosen: Vec<usize> = vec![];
+        for (kind, count) in [(AffixKind::Prefix, prefixes), (AffixKind::Suffix, suffixes)] {
+            for _ in 0..count {
+                // Affissi adatti a questa categoria, con un bonus non ancora scelto
+                let candidates: Vec<usize> = (0..self.affixes.len())
+                    .filter(|&i| {
+                        let a = &self.affixes[i];
+                        a.kind == kind
+                            && a.classes.contains(&b.class)
+                            && chosen.iter().all(|&c| self.affixes[c].bonus != a.bonus)
+                    })
+                    .collect();
+                if !candidates.is_empty() {
+                    chosen.push(candidates[rng.range(0, candidates.len())]);
+                }
+            }
+        }
+        let mods = chosen
+            .iter()
+            .map(|&i| {
+                let a = &self.affixes[i];
+                (
+                    a.bonus,
+                    rng.range(a.min as usize, a.max as usize + 1) as i32,
+                )
+            })
+            .collect();
+
+        let affix = |kind| {
+            chosen
+                .iter()
+                .map(|&i| &self.affixes[i])
+                .find(|a| a.kind == kind)
+                .map(|a| a.name.as_str())
+        };
+        let name = match rarity {
+            Rarity::Rare if !self.rare_names.0.is_empty() && !self.rare_names.1.is_empty() => {
+                let first = &self.rare_names.0[rng.range(0, self.rare_names.0.len())];
+                let second = &self.rare_names.1[rng.range(0, self.rare_names.1.len())];
+                format!("{first} {second}")
+            }
+            // Magico: "Prefisso Base Suffisso"
+            Rarity::Magic => [
+                affix(AffixKind::Prefix),
+                Some(b.name.as_str()),
+                affix(AffixKind::Suffix),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" "),
+            _ => b.name.clone(),
+        };
+
+        Item {
+            base,
+            rarity,
+            name,
+            mods,
+        }
+    }
+
+    // Dimensione nell'inventario di un oggetto
+    pub fn size(&self, item: &Item) -> (usize, usize) {
+        self.bases[item.base].size
+    }
+
+    // Prezzo di un oggetto dal mercante: cresce con danno, difesa, bonus e rarità.
+    // Il mercante compra a 1/SELL_RATIO del prezzo
+    pub fn price(&self, item: &Item) -> u32 {
+        let b = &self.bases[item.base];
+        let weapon = b.damage.map_or(0, |(lo, hi)| (lo + hi) * 2);
+        let mods: i32 = item.mods.iter().map(|&(_, v)| v.abs() * 4).sum();
+        let rarity = match item.rarity {
+            Rarity::Normal => 1,
+            Rarity::Magic => 2,
+            Rarity::Rare => 4,
+            Rarity::Unique => 8,
+        };
+        ((10 + weapon + b.defence * 3 + mods) * rarity) as u32
+    }
+
+    // Bonus totali di un oggetto: valori del tipo base + affissi
+    pub fn bonuses(&self, item: &Item) -> Bonuses {
+        let b = &self.bases[item.base];
+        let mut total = Bonuses {
+            weapon: b.damage,
+            defence: b.defence,
+            ..Bonuses::default()
+        };
+        for &(bonus, value) in &item.mods {
+            total.add(bonus, value);
+        }
+        total
+    }
+}
+
+// Legge i dati sugli oggetti in formato RON
+pub fn parse_items(text: &str) -> Result<ItemData, String> {
+    let data: ItemData = ron::from_str(text).map_err(|e| e.to_string())?;
+    if let Some(b) = data
+        .bases
+        .iter()
+        .find(|b| b.size.0 == 0 || b.size.1 == 0 || b.size.0 > INV_SIZE.0 || b.size.1 > INV_SIZE.1)
+    {
+        return Err(format!("{}: size does not fit the inventory", b.name));
+    }
+    if let Some(a) = data.affixes.iter().find(|a| a.min > a.max || a.min < 0) {
+        return Err(format!("affix {}: invalid range", a.name));
+    }
+    if let Some(u) = data.uniques.iter().find(|u| data.find(&u.base).is_none()) {
+        return Err(format!("unique {}: unknown base {}", u.name, u.base));
+    }
+    for (table, loot) in &data.loot {
+        if let Some((name, _)) = loot.drops.iter().find(|(n, _)| data.find(n).is_none()) {
+            return Err(format!("loot table {table}: unknown item {name}"));
+        }
+    }
+    Ok(data)
+}
+
+// Dati standard (data/items.ron)
+pub fn default_items() -> ItemData {
+    parse_items(ITEMS_RON).unwrap_or_else(|e| panic!("data/items.ron: {e}"))
+}
+
+// Un oggetto concreto
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Item {
+    pub base: usize, // Indice in ItemData::bases
+    pub rarity: Rarity,
+    pub name: String,
+    pub mods: Vec<(Bonus, i32)>, // Bonus degli affissi (o dell'unico)
+}
+
+// Oggetto a terra sulla cella (x, y)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GroundItem {
+    pub x: usize,
+    pub y: usize,
+    pub item: Item,
+}
+
+// Oggetto nell'inventario con la cella del suo angolo in alto a sinistra
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Stored {
+    pub pos: (usize, usize),
+    pub size: (usize, usize),
+    pub item: Item,
+}
+
+impl Stored {
+    // true se l'oggetto copre la cella
+    pub fn covers(&self, cell: (usize, usize)) -> bool {
+        (self.pos.0..self.pos.0 + self.size.0).contains(&cell.0)
+            && (self.pos.1..self.pos.1 + self.size.1).contains(&cell.1)
+    }
+}
+
+// Inventario a griglia
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Inventory {
+    pub items: Vec<Stored>,
+}
+
+impl Inventory {
+    // Indice dell'oggetto che copre la cella (se c'è)
+    pub fn at(&self, cell: (usize, usize)) -> Option<usize> {
+        self.items.iter().position(|s| s.covers(cell))
+    }
+
+    // true se un oggetto di dimensione size sta con l'angolo in pos senza
+    // uscire dalla griglia né sovrapporsi ad altri oggetti (tranne `ignore`,
+    // l'oggetto che si sta spostando)
+    pub fn fits(&self, pos: (usize, usize), size: (usize, usize), ignore: Option<usize>) -> bool {
+        if pos.0 + size.0 > INV_SIZE.0 || pos.1 + size.1 > INV_SIZE.1 {
+            return false;
+        }
+        self.items.iter().enumerate().all(|(i, s)| {
+            Some(i) == ignore
+                || pos.0 >= s.pos.0 + s.size.0
+                || s.pos.0 >= pos.0 + size.0
+                || pos.1 >= s.pos.1 + s.size.1
+                || s.pos.1 >= pos.1 + size.1
+        })
+    }
+
+    // Mette l'oggetto nel primo spazio libero (colonna per colonna, come Diablo).
+    // Se non c'è posto restituisce l'oggetto
+    pub fn insert(&mut self, item: Item, size: (usize, usize)) -> Result<(), Item> {
+        for x in 0..INV_SIZE.0 {
+            for y in 0..INV_SIZE.1 {
+                if self.fits((x, y), size, None) {
+                    self.items.push(Stored {
+                        pos: (x, y),
+                        size,
+                        item,
+                    });
+                    return Ok(());
+                }
+            }
+        }
+        Err(item)
+    }
+
+    // Sposta l'oggetto che copre la cella `from` con l'angolo in `to`.
+    // Ritorna false (e non cambia nulla) se non c'è spazio
+    pub fn move_item(&mut self, from: (usize, usize), to: (usize, usize)) -> bool {
+        let Some(i) = self.at(from) else {
+            return false;
+        };
+        if !self.fits(to, self.items[i].size, Some(i)) {
+            return false;
+        }
+        self.items[i].pos = to;
+        true
+    }
+
+    // Sposta l'oggetto che copre la cella in un'altra griglia (es. dall'inventario
+    // al forziere), nel primo spazio libero. Ritorna false se lì non c'è posto
+    pub fn transfer(&mut self, cell: (usize, usize), to: &mut Inventory) -> bool {
+        let Some(i) = self.at(cell) else {
+            return false;
+        };
+        let stored = self.items.remove(i);
+        match to.insert(stored.item, stored.size) {
+            Ok(()) => true,
+            Err(item) => {
+                self.items.insert(
+                    i,
+                    Stored {
+                        pos: stored.pos,
+                        size: stored.size,
+                        item,
+                    },
+                );
+                false
+            }
+        }
+    }
+
+    // Toglie dall'inventario l'oggetto che copre la cella
+    pub fn take(&mut self, cell: (usize, usize)) -> Option<Item> {
+        let i = self.at(cell)?;
+        Some(self.items.remove(i).item)
+    }
+}
+
+// Oggetti indossati: uno slot per ogni categoria (indice = ItemClass::slot)
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Equipment {
+    pub slots: [Option<Item>; 5],
+}
+
+impl Equipment {
+    // Oggetto indossato nello slot della categoria
+    pub fn get(&self, class: ItemClass) -> Option<&Item> {
+        self.slots[class.slot()].as_ref()
+    }
+
+    // Somma dei bonus di tutto l'equipaggiamento
+    pub fn bonuses(&self, data: &ItemData) -> Bonuses {
+        let mut total = Bonuses::default();
+        for item in self.slots.iter().flatten() {
+            let b = data.bonuses(item);
+            total.weapon = total.weapon.or(b.weapon);
+            for bonus in Bonus::ALL {
+                total.add(bonus, b.get(bonus));
+            }
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item() -> Item {
+        Item {
+            base: 0,
+            rarity: Rarity::Normal,
+            name: "test".into(),
+            mods: vec![],
+        }
+    }
+
+    #[test]
+    fn fits_checks_edges_and_overlaps() {
+        let mut inv = Inventory::default();
+        assert!(inv.fits((0, 0), INV_SIZE, None));
+        assert!(inv.fits((8, 2), (2, 2), None));
+        assert!(!inv.fits((9, 2), (2, 2), None));
+        assert!(!inv.fits((8, 3), (2, 2), None));
+
+        inv.items.push(Stored {
+            pos: (2, 1),
+            size: (2, 3),
+            item: item(),
+        });
+        // Toccare un bordo non è sovrapporsi
+        assert!(inv.fits((0, 1), (2, 3), None));
+        assert!(inv.fits((4, 0), (1, 4), None));
+        assert!(inv.fits((2, 0), (2, 1), None));
+        assert!(!inv.fits((3, 3), (1, 1), None));
+        assert!(!inv.fits((1, 0), (2, 2), None));
+        // L'oggetto che si sta spostando non ostacola se stesso
+        assert!(inv.fits((3, 0), (2, 3), Some(0)));
+    }
+
+    #[test]
+    fn insert_fills_columns_until_full() {
+        let mut inv = Inventory::default();
+        inv.insert(item(), (1, 3)).unwrap();
+        // Colonna per colonna: il primo spazio libero per un 1x1 è sotto
+        inv.insert(item(), (1, 1)).unwrap();
+        assert_eq!(inv.items[1].pos, (0, 3));
+        // Un 2x2 non sta nella prima colonna: va nella seconda
+        inv.insert(item(), (2, 2)).unwrap();
+        assert_eq!(inv.items[2].pos, (1, 0));
+
+        let mut inv = Inventory::default();
+        for _ in 0..INV_SIZE.0 * INV_SIZE.1 / 2 {
+            inv.insert(item(), (1, 2)).unwrap();
+        }
+        assert_eq!(inv.insert(item(), (1, 1)).unwrap_err().name, "test");
+        assert_eq!(inv.items.len(), 20);
+    }
+
+    #[test]
+    fn generated_affixes_stay_in_their_ranges() {
+        let data = default_items();
+        let mut rng = Rng::new(4);
+        let mut rolled = 0;
+        for base in 0..data.bases.len() {
+            let class = data.bases[base].class;
+            for _ in 0..200 {
+                let item = data.generate(base, &mut rng);
+                if item.rarity == Rarity::Unique {
+                    let u = data.uniques.iter().find(|u| u.name == item.name).unwrap();
+                    assert_eq!(item.mods, u.mods);
+                    continue;
+                }
+                let most = match item.rarity {
+                    Rarity::Normal => 0,
+                    Rarity::Magic => 2,
+                    _ => 4,
+                };
+                assert!(item.mods.len() <= most, "{}", item.name);
+                for (i, &(bonus, value)) in item.mods.iter().enumerate() {
+                    // Ogni bonus viene da un affisso adatto, nel suo intervallo
+                    assert!(
+                        data.affixes.iter().any(|a| a.bonus == bonus
+                            && a.classes.contains(&class)
+                            && (a.min..=a.max).contains(&value)),
+                        "{}: {bonus:?} {value}",
+                        item.name
+                    );
+                    assert!(item.mods[..i].iter().all(|&(b, _)| b != bonus));
+                }
+                rolled += item.mods.len();
+            }
+        }
+        assert!(rolled > 0);
+    }
+}
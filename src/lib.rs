@@ -0,0 +1,24 @@
+// Crablo - Clone di Diablo in Rust
+// Bootcamp Rust parte 2 - Francesco Ciulla
+//
+// Libreria con il core del gioco (mappa, generazione, pathfinding, simulazione).
+// Non dipende dalla finestra: la usano sia il gioco (src/main.rs) sia
+// il simulatore headless (src/bin/headless.rs).
+
+pub mod atlas;
+pub mod damage;
+pub mod dungeon;
+pub mod fov;
+pub mod game;
+pub mod item;
+pub mod light;
+pub mod map;
+pub mod monster;
+pub mod path;
+pub mod player;
+pub mod replay;
+pub mod rng;
+pub mod save;
+pub mod skill;
+pub mod status;
+pub mod town;
@@ -0,0 +1,147 @@
+// Luci e mappa della luce
+//
+// Ogni cella ha un livello di luce (rosso, verde, blu tra 0 e 1) che il front
+// end usa per scurire muri, pavimento e personaggi. La luce arriva da:
+// - la luce ambiente del piano (quasi buio nel dungeon, giorno in città)
+// - le sorgenti di luce: il player, le torce sui muri, i bracieri,
+//   proiettili ed esplosioni delle abilità (colorati come l'abilità)
+// Ogni sorgente illumina le celle che "vede" (stesso campo visivo del player,
+// quindi i muri fanno ombra) sempre meno man mano che ci si allontana.
+
+use crate::fov::field_of_view;
+use crate::map::{Grid, Map};
+use crate::rng::Rng;
+use serde::{Deserialize, Serialize};
+
+// Distanza minima tra due torce dello stesso piano (in celle)
+const TORCH_SPACING: usize = 4;
+
+// Luce di una cella: rosso, verde e blu tra 0 e 1
+pub type Rgb = (f32, f32, f32);
+
+// Tipo di torcia: appesa su una faccia di un muro o un braciere a terra
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum TorchKind {
+    East,    // Sulla faccia del muro verso x + 1
+    South,   // Sulla faccia del muro verso y + 1
+    Brazier, // Braciere su una cella calpestabile
+}
+
+// Torcia fissa di un piano: (x, y) è il muro su cui è appesa (o la cella del braciere)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Torch {
+    pub x: usize,
+    pub y: usize,
+    pub kind: TorchKind,
+}
+
+impl Torch {
+    // Cella da cui parte la luce: quella davanti al muro, così la torcia non
+    // illumina anche la stanza dall'altra parte
+    pub fn origin(&self) -> (usize, usize) {
+        match self.kind {
+            TorchKind::East => (self.x + 1, self.y),
+            TorchKind::South => (self.x, self.y + 1),
+            TorchKind::Brazier => (self.x, self.y),
+        }
+    }
+
+    // Luce della torcia: i bracieri fanno più luce delle torce
+    pub fn light(&self) -> Light {
+        let (radius, color) = match self.kind {
+            TorchKind::East | TorchKind::South => (4., (1., 0.6, 0.25)),
+            TorchKind::Brazier => (5., (1., 0.5, 0.2)),
+        };
+        Light {
+            pos: self.origin(),
+            radius,
+            color,
+        }
+    }
+}
+
+// Sorgente di luce: illumina fino a `radius` celle con il suo colore
+#[derive(Copy, Clone, Debug)]
+pub struct Light {
+    pub pos: (usize, usize),
+    pub radius: f32,
+    pub color: Rgb,
+}
+
+// Luce del player in `pos`: una lanterna bianca calda
+pub fn player_light(pos: (usize, usize)) -> Light {
+    Light {
+        pos,
+        radius: 5.,
+        color: (1., 0.9, 0.75),
+    }
+}
+
+// Luce ambiente di un piano: di giorno in città, quasi buio nel dungeon
+pub fn ambient(depth: u32) -> Rgb {
+    if depth == 0 {
+        (0.6, 0.6, 0.55)
+    } else {
+        (0.04, 0.04, 0.07)
+    }
+}
+
+// Numero di torce di un piano w x h: una ogni 100 celle
+pub fn torch_count(w: usize, h: usize) -> usize {
+    w * h / 100
+}
+
+// Sceglie fino a `count` torce sui muri di una mappa, lontane tra loro.
+// Solo sulle facce verso x + 1 e y + 1: sono quelle che si vedono nella
+// vista isometrica
+pub fn place_torches(map: &Map, rng: &mut Rng, count: usize) -> Vec<Torch> {
+    let mut candidates: Vec<Torch> = map
+        .iter()
+        .filter(|&(_, t)| !t.walkable())
+        .filter_map(|((x, y), _)| {
+            let kind = if map.is_walkable(x + 1, y) {
+                TorchKind::East
+            } else if map.is_walkable(x, y + 1) {
+                TorchKind::South
+            } else {
+                return None;
+            };
+            Some(Torch { x, y, kind })
+        })
+        .collect();
+    let mut torches: Vec<Torch> = vec![];
+    while torches.len() < count && !candidates.is_empty() {
+        let t = candidates.swap_remove(rng.range(0, candidates.len()));
+        let far = |o: &Torch| o.x.abs_diff(t.x) + o.y.abs_diff(t.y) >= TORCH_SPACING;
+        if torches.iter().all(far) {
+            torches.push(t);
+        }
+    }
+    torches
+}
+
+// Calcola la luce di ogni cella: luce ambiente più il contributo di ogni
+// sorgente, che cala in modo lineare con la distanza e si ferma ai muri.
+// Il risultato è limitato a 1 per canale
+pub fn light_map(map: &Map, ambient: Rgb, lights: &[Light]) -> Grid<Rgb> {
+    let mut grid = Grid::new(map.width(), map.height(), ambient);
+    for l in lights {
+        if !map.in_bounds(l.pos.0, l.pos.1) {
+            continue;
+        }
+        for (x, y) in field_of_view(map, l.pos, l.radius.ceil() as usize) {
+            let (dx, dy) = (x as f32 - l.pos.0 as f32, y as f32 - l.pos.1 as f32);
+            let f = 1. - (dx * dx + dy * dy).sqrt() / l.radius;
+            if f > 0. {
+                let c = &mut grid[(x, y)];
+                c.0 += l.color.0 * f;
+                c.1 += l.color.1 * f;
+                c.2 += l.color.2 * f;
+            }
+        }
+    }
+    for (_, c) in grid.iter_mut() {
+        *c = (c.0.min(1.), c.1.min(1.), c.2.min(1.));
+    }
+    grid
+}
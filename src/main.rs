@@ -1,151 +1,430 @@
 // Crablo - Clone di Diablo in Rust
 // Bootcamp Rust parte 2 - Francesco Ciulla
+//
+// Front end macroquad: traduce l'input in comandi per la simulazione
+// (crablo::game) e disegna lo stato della partita.
 
-#![allow(dead_code)]
+use crablo::atlas::{self, Atlas, Frame, Pose, Sheet};
+use crablo::damage::{DamageType, Outcome};
+use crablo::dungeon::GenMode;
+use crablo::fov::Visibility;
+use crablo::game::{BLAST_TIME, Command, Game, Projectile, Source, TICK, Target};
+use crablo::item::{Bonus, INV_SIZE, Item, ItemClass, Rarity};
+use crablo::light::{self, Light, Rgb, Torch, TorchKind};
+use crablo::map::{Grid, Map, Tile};
+use crablo::monster::{MonsterKind, Sprite};
+use crablo::player::Stat;
+use crablo::replay::{Playback, Replay};
+use crablo::save::{self, SaveFormat};
+use crablo::skill::HOTBAR_SIZE;
+use crablo::status::{Effects, StatusKind};
+use crablo::town::Role;
 use macroquad::prelude::*;
-use std::collections::VecDeque;
+use std::collections::HashMap;
 
-// Dimensione della griglia di gioco (20x20 celle)
-const MAP: usize = 20;
+// Dimensione (larghezza, altezza) della mappa per una nuova partita.
+// La mappa è allocata a runtime: qualsiasi dimensione (anche non quadrata) funziona.
+const MAP_SIZE: (usize, usize) = (20, 20);
 // Dimensione del tile isometrico (larghezza, altezza)
 // La vista isometrica usa un rapporto 2:1 (32 pixel largo, 16 alto)
 const T_SIZE: (f32, f32) = (32., 16.);
+// File in cui viene salvato il replay dell'ultima partita
+const REPLAY_FILE: &str = "crablo.replay";
+// File di salvataggio della partita (RON o binario, riconosciuto al caricamento)
+const SAVE_FILE: &str = "crablo.save";
+// Cartella delle immagini facoltative (vedi crablo::atlas)
+const ASSETS_DIR: &str = "assets";
 
 // Enum per gestire gli stati del gioco (state machine)
 enum AppState {
     Menu,     // Schermata iniziale
     Playing,  // Partita in corso
     GameOver, // Fine partita
+    Replay,   // Riproduzione di una partita registrata
 }
 
-// Enum per i tipi di celle della mappa
-#[derive(Copy, Clone, PartialEq)]
-enum Tile {
-    Wall,  // Muro: blocca il movimento
-    Floor, // Pavimento: calpestabile
+// Converte coordinate griglia (x, y) → coordinate del mondo (sx, sy), in pixel
+// a zoom 1. Formula isometrica: la X dipende dalla differenza (x-y),
+// la Y dipende dalla somma (x+y). Dal mondo allo schermo ci pensa la Camera.
+fn to_screen(x: usize, y: usize) -> (f32, f32) {
+    to_screen_f(x as f32, y as f32)
 }
 
-// Struttura per i mostri nemici
-struct Monster {
-    x: usize, // Posizione X sulla griglia
-    y: usize, // Posizione Y sulla griglia
-    hp: i32,  // Punti vita
-    cd: f32,  // Cooldown per azioni (attacco/movimento)
+// Come to_screen, ma con coordinate griglia decimali (es. un personaggio
+// a metà strada tra due celle durante l'animazione del passo)
+fn to_screen_f(x: f32, y: f32) -> (f32, f32) {
+    ((x - y) * T_SIZE.0, (x + y) * T_SIZE.1)
 }
 
-// Struttura per il testo fluttuante del danno (floating damage text)
-// Mostra "-10" che sale e scompare quando colpisci un mostro
-struct DmgText {
-    x: f32,    // Posizione X sullo schermo
-    y: f32,    // Posizione Y sullo schermo (sale nel tempo)
-    dmg: i32,  // Quantità di danno da mostrare
-    life: f32, // Tempo rimanente prima che il testo scompaia (in secondi)
+// Inverso di to_screen: converte coordinate del mondo → coordinate griglia
+// (per il mouse: prima Camera::to_world, poi to_tile).
+// Il rombo della cella (x, y) ha il vertice in alto in to_screen(x, y), quindi
+// i valori con decimali si arrotondano per difetto (floor, non `as usize` che
+// tronca verso zero). None fuori dalla mappa, anche a sinistra o sopra
+fn to_tile(p: Vec2, map: &Map) -> Option<(usize, usize)> {
+    let (u, v) = (p.x / T_SIZE.0, p.y / T_SIZE.1);
+    let (x, y) = (((u + v) / 2.).floor(), ((v - u) / 2.).floor());
+    if x < 0. || y < 0. {
+        return None;
+    }
+    let (x, y) = (x as usize, y as usize);
+    map.in_bounds(x, y).then_some((x, y))
 }
 
-// Converte coordinate griglia (x, y) → coordinate schermo (sx, sy)
-// Formula isometrica: la X schermo dipende dalla differenza (x-y),
-// la Y schermo dipende dalla somma (x+y). cam è l'offset della camera.
-fn to_screen(x: usize, y: usize, cam: (f32, f32)) -> (f32, f32) {
-    (
-        (x as f32 - y as f32) * T_SIZE.0 + cam.0,
-        (x as f32 + y as f32) * T_SIZE.1 + cam.1,
+// Riquadro nel mondo occupato da uno stickman in pos (scale: dimensione)
+fn sprite_rect(pos: (f32, f32), scale: f32) -> Rect {
+    let (sx, sy) = to_screen_f(pos.0, pos.1);
+    let feet = sy + T_SIZE.1;
+    Rect::new(
+        sx - 10. * scale,
+        feet - 42. * scale,
+        20. * scale,
+        42. * scale + 4.,
     )
 }
 
-// Inverso di to_screen: converte coordinate schermo → coordinate griglia
-fn to_tile(sx: f32, sy: f32, cam: (f32, f32)) -> (usize, usize) {
-    let (ax, ay) = (sx - cam.0, sy - cam.1);
-    (
-        ((ax / T_SIZE.0 + ay / T_SIZE.1) / 2.) as usize,
-        ((ay / T_SIZE.1 - ax / T_SIZE.0) / 2.) as usize,
-    )
+// Cella puntata dal mouse (p in coordinate del mondo). Prima si cercano i
+// personaggi visibili sotto il mouse, così si può cliccare anche la testa di un
+// mostro che sporge sulla cella dietro (se se ne sovrappongono più di uno vince
+// quello davanti); altrimenti la cella del rombo sotto il mouse, ma solo se è
+// già stata esplorata (il buio non deve rivelare dove sono muri e pavimento)
+fn pick(game: &Game, p: Vec2, alpha: f32) -> Option<(usize, usize)> {
+    let monsters = game.monsters.iter().map(|m| {
+        let scale = game.kinds[m.kind].scale;
+        ((m.x, m.y), sprite_rect(m.draw_pos(alpha), scale))
+    });
+    let npcs = game.npcs.iter().map(|n| {
+        let cell = (n.x, n.y);
+        (cell, sprite_rect((n.x as f32, n.y as f32), 1.))
+    });
+    monsters
+        .chain(npcs)
+        .filter(|&(cell, rect)| game.fog.visible(cell) && rect.contains(p))
+        .max_by_key(|&(cell, _)| cell.0 + cell.1)
+        .map(|(cell, _)| cell)
+        .or_else(|| to_tile(p, &game.map))
+        .filter(|&cell| game.fog.get(cell) != Visibility::Unexplored)
 }
 
-// Calcola la distanza Manhattan tra due punti sulla griglia
-// La distanza Manhattan è la somma delle differenze assolute delle coordinate:
-// |x1-x2| + |y1-y2|. Si chiama così perché rappresenta la distanza percorsa
-// in una griglia (come le strade di Manhattan), dove puoi muoverti solo
-// in orizzontale o verticale, mai in diagonale.
-// Usata per determinare se un mostro è adiacente al player (distanza = 1)
-fn dist(p1: (usize, usize), p2: (usize, usize)) -> i32 {
-    (p1.0 as i32 - p2.0 as i32).abs() + (p1.1 as i32 - p2.1 as i32).abs()
+// Forma del puntatore del mouse sulla mappa
+#[derive(Copy, Clone, PartialEq)]
+enum Cursor {
+    Attack,  // Spada: sopra un mostro
+    Walk,    // Freccia: sopra una cella raggiungibile (o un NPC)
+    Blocked, // Divieto: sopra un muro o una cella mai vista
 }
 
-// Pathfinding: Breadth-First Search (BFS)
-// Trova il percorso più breve tra start e goal evitando i muri.
-// Ritorna un Vec con le coordinate del percorso (escluso start, incluso goal).
-// Ritorna vec vuoto se non esiste un percorso.
-//
-// BFS esplora "a onde concentriche": prima tutte le celle a distanza 1,
-// poi quelle a distanza 2, ecc. La coda FIFO (First In, First Out) garantisce
-// questo ordine. Se usassimo uno stack LIFO avremmo DFS (Depth-First Search),
-// che va "in profondità" e non garantisce il percorso più breve.
-fn bfs(
-    map: &[[Tile; MAP]; MAP],
-    start: (usize, usize),
-    goal: (usize, usize),
-) -> Vec<(usize, usize)> {
-    // Coda FIFO: celle da esplorare. BFS usa FIFO per garantire il percorso più breve.
-    let mut q = VecDeque::from([start]);
-
-    // Matrice visited: traccia le celle già visitate per evitare loop infiniti.
-    // Senza questo, l'algoritmo continuerebbe a visitare le stesse celle (dead loop).
-    let mut visited = [[false; MAP]; MAP];
-    visited[start.1][start.0] = true;
-
-    // Matrice parent: per ogni cella, memorizza da quale cella ci siamo arrivati.
-    // Serve per ricostruire il percorso una volta raggiunto il goal.
-    let mut parent: [[Option<(usize, usize)>; MAP]; MAP] = [[None; MAP]; MAP];
-
-    // Estrai celle dalla coda finché non è vuota
-    while let Some(curr) = q.pop_front() {
-        // Se abbiamo raggiunto il goal, ricostruiamo il percorso
-        if curr == goal {
-            let mut path = vec![];
-            let mut c = goal;
-            // Risaliamo i parent dal goal fino allo start
-            while c != start {
-                path.push(c);
-                c = parent[c.1][c.0].unwrap();
-            }
-            // Il percorso è al contrario (goal→start), lo invertiamo
-            path.reverse();
-            return path;
+// Puntatore per la cella sotto il mouse
+fn cursor_for(game: &Game, cell: (usize, usize)) -> Cursor {
+    let monster = game.monsters.iter().any(|m| (m.x, m.y) == cell);
+    if monster && game.fog.visible(cell) {
+        Cursor::Attack
+    } else if game.fog.get(cell) == Visibility::Unexplored {
+        // Mai vista: non si dice se è un muro o un pavimento
+        Cursor::Blocked
+    } else if game.map.is_walkable(cell.0, cell.1) {
+        Cursor::Walk
+    } else {
+        Cursor::Blocked
+    }
+}
+
+// Disegna il puntatore in (x, y) sullo schermo (la punta è lì)
+fn draw_cursor(cursor: Cursor, x: f32, y: f32) {
+    match cursor {
+        Cursor::Attack => {
+            // Lama dalla punta verso il basso a destra, guardia ed elsa
+            draw_line(x, y, x + 13., y + 13., 3., LIGHTGRAY);
+            draw_line(x + 8., y + 16., x + 16., y + 8., 3., GOLD);
+            draw_line(x + 13., y + 13., x + 19., y + 19., 4., BROWN);
+        }
+        Cursor::Walk => {
+            let (a, b, c) = (vec2(x, y), vec2(x, y + 16.), vec2(x + 11., y + 11.));
+            draw_triangle(a, b, c, WHITE);
+            draw_triangle_lines(a, b, c, 1.5, BLACK);
+        }
+        Cursor::Blocked => {
+            let (cx, cy) = (x + 8., y + 8.);
+            draw_circle_lines(cx, cy, 7., 2.5, RED);
+            draw_line(cx - 5., cy + 5., cx + 5., cy - 5., 2.5, RED);
         }
+    }
+}
 
-        // Esplora i 4 vicini (su, giù, sinistra, destra)
-        for (dx, dy) in [(0, -1), (0, 1), (-1, 0), (1, 0)] {
-            let (nx, ny) = ((curr.0 as i32 + dx) as usize, (curr.1 as i32 + dy) as usize);
+// Immagini caricate all'avvio: ciò che manca resta disegnato in modo procedurale
+#[derive(Default)]
+struct Assets {
+    atlas: Atlas,
+    tiles: Option<Texture2D>,           // Immagine dell'atlante delle celle
+    sheets: HashMap<String, Texture2D>, // Sprite sheet per nome del personaggio
+}
 
-            // Controlla: dentro i bounds, non già visitata, non è un muro
-            if nx < MAP && ny < MAP && !visited[ny][nx] && map[ny][nx] == Tile::Floor {
-                visited[ny][nx] = true; // Marca come visitata PRIMA di aggiungere alla coda
-                parent[ny][nx] = Some(curr); // Ricorda da dove siamo arrivati
-                q.push_back((nx, ny)); // Aggiungi alla coda per esplorarla dopo
+impl Assets {
+    // Carica atlas.ron dalla cartella dir e le immagini che descrive.
+    // Senza il file non c'è niente da caricare; un file rotto o un'immagine
+    // mancante vengono segnalati, ma il gioco parte lo stesso
+    async fn load(dir: &str) -> Self {
+        let path = format!("{dir}/atlas.ron");
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            return Assets::default();
+        };
+        let atlas = match Atlas::parse(&text) {
+            Ok(atlas) => atlas,
+            Err(e) => {
+                eprintln!("Cannot read {path}: {e}");
+                return Assets::default();
             }
+        };
+        let tiles = if atlas.tiles.is_empty() {
+            None
+        } else {
+            load_image(dir, &atlas.image).await
+        };
+        let mut sheets = HashMap::new();
+        for (name, sheet) in &atlas.sheets {
+            if let Some(texture) = load_image(dir, &sheet.image).await {
+                sheets.insert(name.clone(), texture);
+            }
+        }
+        Assets {
+            atlas,
+            tiles,
+            sheets,
         }
     }
-    // Coda vuota e goal non raggiunto = nessun percorso possibile
-    vec![]
+
+    // Pezzo `name` dell'atlante, se c'è sia la voce sia l'immagine
+    fn tile(&self, name: &str) -> Option<(&Texture2D, &Frame)> {
+        Some((self.tiles.as_ref()?, self.atlas.tiles.get(name)?))
+    }
+
+    // Sprite sheet del personaggio `name`, se è stato caricato
+    fn sheet(&self, name: &str) -> Option<(&Texture2D, &Sheet)> {
+        Some((self.sheets.get(name)?, self.atlas.sheets.get(name)?))
+    }
+}
+
+// Carica un'immagine PNG della cartella dir; se non ci riesce lo dice e ritorna None
+async fn load_image(dir: &str, file: &str) -> Option<Texture2D> {
+    let path = format!("{dir}/{file}");
+    match load_texture(&path).await {
+        Ok(texture) => {
+            // Pixel art: nessuna sfocatura quando la camera ingrandisce
+            texture.set_filter(FilterMode::Nearest);
+            Some(texture)
+        }
+        Err(e) => {
+            eprintln!("Cannot load {path}: {e}");
+            None
+        }
+    }
+}
+
+// Disegna il pezzo `source` (x, y, larghezza, altezza) di un'immagine
+// mettendo il suo punto `origin` in `at`, ingrandito di `scale`.
+// color moltiplica i pixel: luce della cella, tinta degli effetti, trasparenza
+fn draw_image(
+    texture: &Texture2D,
+    source: (f32, f32, f32, f32),
+    origin: (f32, f32),
+    at: (f32, f32),
+    scale: f32,
+    color: Color,
+) {
+    let (x, y, w, h) = source;
+    draw_texture_ex(
+        texture,
+        at.0 - origin.0 * scale,
+        at.1 - origin.1 * scale,
+        color,
+        DrawTextureParams {
+            source: Some(Rect::new(x, y, w, h)),
+            dest_size: Some(vec2(w * scale, h * scale)),
+            ..Default::default()
+        },
+    );
+}
+
+// Disegna la cella (x, y) con il pezzo `name` dell'atlante.
+// Ritorna false se manca, così chi chiama la disegna in modo procedurale
+fn draw_tile_image(assets: &Assets, name: &str, x: usize, y: usize, light: Color) -> bool {
+    let Some((texture, frame)) = assets.tile(name) else {
+        return false;
+    };
+    let (sx, sy) = to_screen(x, y);
+    draw_image(
+        texture,
+        frame.rect,
+        frame.origin,
+        (sx, sy + T_SIZE.1),
+        1.,
+        light,
+    );
+    true
+}
+
+// Chi disegnare con draw_stickman
+#[derive(Copy, Clone)]
+enum Figure<'a> {
+    Player,                   // Testa tonda, chiaro
+    Monster(&'a MonsterKind), // Colore, testa e dimensione del suo tipo
+    Npc(Role),                // Tunica lunga al posto delle gambe, colore del ruolo
+}
+
+// Colore della tunica di un NPC
+fn npc_color(role: Role) -> Color {
+    match role {
+        Role::Healer => Color::new(0.85, 0.85, 0.9, 1.),
+        Role::Merchant => Color::new(0.55, 0.35, 0.15, 1.),
+        Role::Stash => Color::new(0.5, 0.3, 0.1, 1.),
+    }
 }
 
-// Disegna uno stickman (player o mostro)
-// enemy=true: disegna con corna (mostro), enemy=false: disegna con testa tonda (player)
-fn draw_stickman(x: usize, y: usize, cam: (f32, f32), enemy: bool) {
-    let (sx, mut sy) = to_screen(x, y, cam);
+// Tinta dell'effetto più importante (lo stordimento prima di tutti):
+// il colore del personaggio si mescola a metà con quello dell'effetto
+fn status_tint(color: Color, effects: &Effects) -> Color {
+    match StatusKind::ALL.iter().rev().find(|&&k| effects.has(k)) {
+        Some(&status) => {
+            let t = status_color(status);
+            Color::new(
+                (color.r + t.r) / 2.,
+                (color.g + t.g) / 2.,
+                (color.b + t.b) / 2.,
+                1.,
+            )
+        }
+        None => color,
+    }
+}
+
+// Disegna un personaggio con il suo sprite sheet, o come stickman se manca.
+// pose: direzione in cui guarda e se sta camminando
+// t: tempo in secondi, fa scorrere i fotogrammi della camminata
+fn draw_actor(
+    assets: &Assets,
+    pos: (f32, f32),
+    figure: Figure,
+    effects: &Effects,
+    light: Color,
+    pose: Pose,
+    t: f32,
+) {
+    let (name, scale) = match figure {
+        Figure::Player => ("player", 1.),
+        Figure::Monster(k) => (k.name.as_str(), k.scale),
+        Figure::Npc(role) => (role.name(), 1.),
+    };
+    let Some((texture, sheet)) = assets.sheet(name) else {
+        draw_stickman(pos, figure, effects, light);
+        return;
+    };
+    let (sx, sy) = to_screen_f(pos.0, pos.1);
+    let feet = (sx, sy + T_SIZE.1);
+    // Ombra a terra, come per lo stickman
+    draw_ellipse(
+        feet.0,
+        feet.1 + 3.,
+        10. * scale,
+        5. * scale,
+        0.,
+        Color::new(0., 0., 0., 0.2),
+    );
+    // L'immagine ha già i suoi colori: la luce e gli effetti la tingono
+    let color = shade(status_tint(WHITE, effects), light);
+    let source = sheet.source(pose.dir, sheet.frame(pose.moving, t));
+    draw_image(texture, source, sheet.origin, feet, scale, color);
+}
+
+// Disegna uno stickman (player, mostro o NPC)
+// pos: posizione in coordinate griglia (con decimali, per il movimento fluido)
+// figure: chi è, e quindi colore, testa e dimensione
+// effects: effetti di stato attivi, che colorano il personaggio
+// light: luce della cella in cui si trova
+fn draw_stickman(pos: (f32, f32), figure: Figure, effects: &Effects, light: Color) {
+    let (sx, mut sy) = to_screen_f(pos.0, pos.1);
     sy += 16.;
+    let (color, s) = match figure {
+        Figure::Player => (Color::new(0.95, 0.92, 0.85, 1.), 1.),
+        Figure::Monster(k) => (Color::new(k.color.0, k.color.1, k.color.2, 1.), k.scale),
+        Figure::Npc(role) => (npc_color(role), 1.),
+    };
+    let color = shade(status_tint(color, effects), light);
 
     // Ombra a terra
-    draw_ellipse(sx, sy + 3., 10., 5., 0., Color::new(0., 0., 0., 0.2));
+    draw_ellipse(
+        sx,
+        sy + 3.,
+        10. * s,
+        5. * s,
+        0.,
+        Color::new(0., 0., 0., 0.2),
+    );
 
-    // Testa: corna per i nemici, cerchio per il player
-    if enemy {
-        // Corna del mostro (due linee a V)
-        draw_line(sx - 5., sy - 32., sx, sy - 30., 2., BLACK);
-        draw_line(sx + 5., sy - 32., sx, sy - 30., 2., BLACK);
-    } else {
-        // Testa tonda del player
-        draw_circle_lines(sx, sy - 32., 7., 2., BLACK);
+    // NPC: tunica triangolare, braccia e testa piena, più il segno del ruolo
+    if let Figure::Npc(role) = figure {
+        draw_triangle(
+            vec2(sx, sy - 25.),
+            vec2(sx - 9., sy),
+            vec2(sx + 9., sy),
+            color,
+        );
+        draw_triangle_lines(
+            vec2(sx, sy - 25.),
+            vec2(sx - 9., sy),
+            vec2(sx + 9., sy),
+            1.,
+            DARKGRAY,
+        );
+        let skin = shade(Color::new(0.95, 0.8, 0.65, 1.), light);
+        draw_line(sx, sy - 20., sx - 8., sy - 14., 2., skin);
+        draw_line(sx, sy - 20., sx + 8., sy - 14., 2., skin);
+        draw_circle(sx, sy - 31., 6., skin);
+        match role {
+            // Croce rossa sulla tunica del guaritore
+            Role::Healer => {
+                let red = shade(RED, light);
+                draw_line(sx, sy - 16., sx, sy - 6., 2., red);
+                draw_line(sx - 4., sy - 12., sx + 4., sy - 12., 2., red);
+            }
+            // Cappello a tesa larga del mercante
+            Role::Merchant => {
+                let hat = shade(DARKBROWN, light);
+                draw_rectangle(sx - 9., sy - 37., 18., 2., hat);
+                draw_rectangle(sx - 5., sy - 42., 10., 5., hat);
+            }
+            Role::Stash => {}
+        }
+        return;
+    }
+
+    // Testa: dipende dal tipo di mostro, cerchio per il player
+    match figure {
+        Figure::Monster(k) => match k.sprite {
+            Sprite::Horns => {
+                // Corna del mostro (due linee a V)
+                draw_line(sx - 5. * s, sy - 32. * s, sx, sy - 30. * s, 2., color);
+                draw_line(sx + 5. * s, sy - 32. * s, sx, sy - 30. * s, 2., color);
+            }
+            Sprite::Skull => {
+                // Teschio: cerchio pieno con due occhi vuoti
+                draw_circle(sx, sy - 31. * s, 6. * s, color);
+                draw_circle(sx - 2.5 * s, sy - 32. * s, 1.5 * s, WHITE);
+                draw_circle(sx + 2.5 * s, sy - 32. * s, 1.5 * s, WHITE);
+            }
+            Sprite::Hood => {
+                // Cappuccio: triangolo a punta sopra la testa
+                draw_triangle(
+                    vec2(sx, sy - 42. * s),
+                    vec2(sx - 7. * s, sy - 26. * s),
+                    vec2(sx + 7. * s, sy - 26. * s),
+                    color,
+                );
+            }
+        },
+        _ => {
+            // Testa tonda del player
+            draw_circle_lines(sx, sy - 32., 7., 2., color);
+        }
     }
     // Corpo e arti: array di linee [x1, y1, x2, y2] relative a (sx, sy)
     // Linea 0: corpo (collo → bacino)
@@ -158,14 +437,172 @@ fn draw_stickman(x: usize, y: usize, cam: (f32, f32), enemy: bool) {
         [0., -8., -6., 0.],    // gamba sinistra
         [0., -8., 6., 0.],     // gamba destra
     ] {
-        draw_line(sx + l[0], sy + l[1], sx + l[2], sy + l[3], 2., BLACK);
+        draw_line(
+            sx + l[0] * s,
+            sy + l[1] * s,
+            sx + l[2] * s,
+            sy + l[3] * s,
+            2.,
+            color,
+        );
+    }
+}
+
+// Disegna il forziere della città: una cassa con coperchio e serratura dorata
+fn draw_chest(x: usize, y: usize, light: Color) {
+    let (sx, sy) = to_screen(x, y);
+    let (cx, cy) = (sx, sy + T_SIZE.1);
+    let wood = shade(npc_color(Role::Stash), light);
+    draw_rectangle(cx - 11., cy - 12., 22., 14., wood);
+    draw_rectangle(cx - 12., cy - 16., 24., 5., shade(DARKBROWN, light));
+    draw_rectangle_lines(cx - 11., cy - 12., 22., 14., 1., BLACK);
+    draw_rectangle(cx - 2., cy - 10., 4., 5., shade(GOLD, light));
+}
+
+// Fiamma di una torcia o di un braciere, alla base (x, y) sullo schermo.
+// t: tempo in secondi, fa tremolare la fiamma
+fn draw_flame(x: f32, y: f32, t: f32) {
+    let h = 7. + (t * 12. + x).sin() * 1.5;
+    draw_triangle(
+        vec2(x - 3., y),
+        vec2(x + 3., y),
+        vec2(x, y - h),
+        Color::new(1., 0.55, 0.1, 1.),
+    );
+    draw_circle(x, y - 1., 2., Color::new(1., 0.9, 0.4, 1.));
+}
+
+// Disegna una torcia: un supporto sulla faccia del muro con la fiamma sopra,
+// oppure un braciere (una coppa su un piede) con il fuoco dentro
+fn draw_torch(torch: &Torch, light: Color, t: f32) {
+    let (sx, sy) = to_screen(torch.x, torch.y);
+    let iron = shade(Color::new(0.35, 0.3, 0.25, 1.), light);
+    match torch.kind {
+        // Centro della faccia destra (verso x + 1) o sinistra (verso y + 1) del cubo
+        TorchKind::East | TorchKind::South => {
+            let side = if torch.kind == TorchKind::East {
+                16.
+            } else {
+                -16.
+            };
+            let (x, y) = (sx + side, sy - 4.);
+            draw_line(x, y + 6., x, y, 3., iron);
+            draw_flame(x, y, t);
+        }
+        TorchKind::Brazier => {
+            let (x, y) = (sx, sy + T_SIZE.1);
+            draw_line(x, y, x, y - 8., 3., iron);
+            draw_triangle(
+                vec2(x - 7., y - 14.),
+                vec2(x + 7., y - 14.),
+                vec2(x, y - 7.),
+                iron,
+            );
+            draw_flame(x, y - 13., t);
+        }
+    }
+}
+
+// Colore di un effetto di stato (tinta dei personaggi e icone dell'HUD)
+fn status_color(kind: StatusKind) -> Color {
+    match kind {
+        StatusKind::Poison => Color::new(0.2, 0.8, 0.1, 1.),
+        StatusKind::Burn => Color::new(1., 0.4, 0., 1.),
+        StatusKind::Slow => Color::new(0.3, 0.6, 1., 1.),
+        StatusKind::Stun => Color::new(1., 0.9, 0., 1.),
+    }
+}
+
+// Colore dei numeri di danno per tipo
+fn damage_color(kind: DamageType) -> Color {
+    match kind {
+        DamageType::Physical => RED,
+        DamageType::Fire => ORANGE,
+        DamageType::Cold => Color::new(0.2, 0.5, 1., 1.),
+        DamageType::Lightning => Color::new(0.9, 0.8, 0., 1.),
+        DamageType::Poison => GREEN,
+    }
+}
+
+// Luce delle celle ricordate ma fuori dal campo visivo (grigio bluastro)
+const REMEMBERED: Color = Color::new(0.25, 0.25, 0.3, 1.);
+
+// Luce minima delle celle nel campo visivo: anche al buio se ne intuisce la forma
+const MIN_LIGHT: f32 = 0.15;
+
+// Sfondo fuori dalle zone illuminate
+const BACKGROUND: Color = Color::new(0.03, 0.03, 0.05, 1.);
+
+// Colore illuminato: ogni canale moltiplicato per quello della luce
+// (luce bianca = colore invariato, luce nera = nero).
+// L'alfa della luce rende il colore trasparente (muri in cutaway)
+fn shade(c: Color, light: Color) -> Color {
+    Color::new(c.r * light.r, c.g * light.g, c.b * light.b, c.a * light.a)
+}
+
+// Luce con cui disegnare la cella: None se mai vista, REMEMBERED se solo
+// ricordata, altrimenti la luce della mappa (almeno MIN_LIGHT)
+fn tile_light(game: &Game, lights: &Grid<Rgb>, cell: (usize, usize)) -> Option<Color> {
+    match game.fog.get(cell) {
+        Visibility::Unexplored => None,
+        Visibility::Explored => Some(REMEMBERED),
+        Visibility::Visible => {
+            let (r, g, b) = lights[cell];
+            Some(Color::new(
+                r.max(MIN_LIGHT),
+                g.max(MIN_LIGHT),
+                b.max(MIN_LIGHT),
+                1.,
+            ))
+        }
+    }
+}
+
+// Cella della griglia più vicina a una posizione con decimali
+fn nearest_cell(pos: (f32, f32)) -> (usize, usize) {
+    (
+        pos.0.round().max(0.) as usize,
+        pos.1.round().max(0.) as usize,
+    )
+}
+
+// Sorgenti di luce del momento: player, torce (con un leggero tremolio),
+// proiettili ed esplosioni colorati come chi li ha lanciati
+fn lights(game: &Game, t: f32) -> Vec<Light> {
+    let mut list = vec![light::player_light((game.px, game.py))];
+    for torch in &game.torches {
+        let mut l = torch.light();
+        l.radius *= 1. + (t * 7. + torch.x as f32 * 3.).sin() * 0.04;
+        list.push(l);
     }
+    for p in &game.projectiles {
+        let (color, radius) = match p.source {
+            Source::Monster(kind) => (game.kinds[kind].color, 1.5),
+            Source::Player(skill) => (game.skills[skill].color, 2.5),
+        };
+        list.push(Light {
+            pos: nearest_cell(p.pos),
+            radius,
+            color,
+        });
+    }
+    for b in &game.blasts {
+        let c = game.skills[b.skill].color;
+        let f = b.life / BLAST_TIME;
+        list.push(Light {
+            pos: nearest_cell(b.pos),
+            radius: b.radius + 2.,
+            color: (c.0 * f, c.1 * f, c.2 * f),
+        });
+    }
+    list
 }
 
 // Disegna un muro 3D isometrico (cubo con 3 facce visibili)
 // Il muro è composto da triangoli per creare l'effetto 3D
-fn draw_wall(x: usize, y: usize, cam: (f32, f32)) {
-    let (sx, sy) = to_screen(x, y, cam);
+// light: luce della cella, che scurisce e colora le facce
+fn draw_wall(x: usize, y: usize, light: Color) {
+    let (sx, sy) = to_screen(x, y);
 
     // Vertici del cubo isometrico (7 punti)
     // v[0]: punto più alto (cima del cubo)
@@ -186,7 +623,8 @@ fn draw_wall(x: usize, y: usize, cam: (f32, f32)) {
         Color::new(0.8, 0.8, 0.8, 1.), // top: più chiaro
         Color::new(0.5, 0.5, 0.5, 1.), // right: più scuro
         Color::new(0.6, 0.6, 0.6, 1.), // left: medio
-    ];
+    ]
+    .map(|c| shade(c, light));
 
     // Disegna le 3 facce con triangoli (2 triangoli per faccia)
     // Faccia superiore (top)
@@ -201,314 +639,1475 @@ fn draw_wall(x: usize, y: usize, cam: (f32, f32)) {
 
     // Disegna i bordi neri per definire il contorno
     for (a, b) in [(0, 1), (1, 2), (2, 3), (3, 0), (1, 4), (2, 5), (3, 6)] {
-        draw_line(v[a].x, v[a].y, v[b].x, v[b].y, 1., BLACK);
-    }
-}
-
-// Struttura principale del gioco: contiene tutto lo stato di una partita
-struct Game {
-    map: [[Tile; MAP]; MAP], // Griglia della mappa (Wall o Floor)
-    cam: (f32, f32),         // Offset camera per centrare la vista
-    px: usize,               // Posizione X del player sulla griglia
-    py: usize,               // Posizione Y del player sulla griglia
-    // Percorso calcolato da BFS: lista di celle da attraversare per raggiungere il target
-    path: Vec<(usize, usize)>,
-    // Cooldown movimento: tempo rimanente prima del prossimo passo (in secondi)
-    player_cd: f32,
-    // Lista dei mostri presenti nella mappa
-    monsters: Vec<Monster>,
-    // Lista dei testi di danno fluttuanti attivi
-    texts: Vec<DmgText>,
-    // Punti vita del player (game over quando <= 0)
-    hp: i32,
-}
-
-impl Game {
-    // Crea una nuova partita con mappa, player e mostri inizializzati
-    fn new() -> Self {
-        // Inizializza tutta la mappa come pavimento
-        let mut map = [[Tile::Floor; MAP]; MAP];
-
-        // Crea i muri perimetrali (bordi della mappa)
-        for i in 0..MAP {
-            map[0][i] = Tile::Wall; // bordo superiore
-            map[MAP - 1][i] = Tile::Wall; // bordo inferiore
-            map[i][0] = Tile::Wall; // bordo sinistro
-            map[i][MAP - 1] = Tile::Wall; // bordo destro
-        }
-
-        // Aggiungi ostacoli interni (muri sparsi)
-        for (x, y) in [(5, 5), (6, 5), (12, 10)] {
-            map[y][x] = Tile::Wall;
-        }
-
-        Game {
-            map,
-            cam: (screen_width() / 2., 50.),
-            px: 2,
-            py: 2,
-            path: vec![],
-            player_cd: 0.,
-            // Spawn dei mostri in posizioni fisse sulla mappa
-            monsters: vec![
-                Monster {
-                    x: 8,
-                    y: 8,
-                    hp: 30,
-                    cd: 0.,
-                },
-                Monster {
-                    x: 12,
-                    y: 4,
-                    hp: 30,
-                    cd: 0.,
-                },
-                Monster {
-                    x: 15,
-                    y: 12,
-                    hp: 30,
-                    cd: 0.,
-                },
-            ],
-            texts: vec![],
-            hp: 100, // Player inizia con 100 HP
-        }
-    }
-
-    // Aggiorna lo stato del gioco ogni frame
-    // dt = delta time (tempo trascorso dall'ultimo frame)
-    // Ritorna true se il gioco deve terminare (game over)
-    fn update(&mut self, dt: f32) -> bool {
-        // Controllo game over: se HP <= 0, la partita finisce
-        if self.hp <= 0 {
-            return true;
-        }
-
-        // Aggiorna animazione testi di danno fluttuanti
-        // retain_mut mantiene solo i testi con life > 0, rimuovendo quelli scaduti
-        self.texts.retain_mut(|t| {
-            t.life -= dt; // Decrementa il tempo di vita
-            t.y -= 20. * dt; // Fa salire il testo verso l'alto
-            t.life > 0. // Ritorna true se il testo deve rimanere
-        });
+        draw_line(
+            v[a].x,
+            v[a].y,
+            v[b].x,
+            v[b].y,
+            1.,
+            Color {
+                a: light.a,
+                ..BLACK
+            },
+        );
+    }
+}
 
-        // Input mouse: al click sinistro, calcola il percorso verso la cella cliccata
-        if is_mouse_button_pressed(MouseButton::Left) {
-            let (mx, my) = mouse_position();
-            // Converte coordinate schermo → coordinate griglia
-            let (tx, ty) = to_tile(mx, my, self.cam);
+// Disegna un rombo isometrico piatto sul pavimento della cella (x, y)
+// Il centro del rombo coincide con il punto dove stanno i personaggi (sy + 16)
+fn draw_floor_tile(x: usize, y: usize, color: Color) {
+    let (sx, sy) = to_screen(x, y);
+    let (top, right) = (vec2(sx, sy), vec2(sx + T_SIZE.0, sy + T_SIZE.1));
+    let (bottom, left) = (
+        vec2(sx, sy + 2. * T_SIZE.1),
+        vec2(sx - T_SIZE.0, sy + T_SIZE.1),
+    );
+    draw_triangle(top, right, bottom, color);
+    draw_triangle(top, bottom, left, color);
+}
 
-            // Verifica: dentro i bounds e non è un muro
-            if tx < MAP && ty < MAP && self.map[ty][tx] == Tile::Floor {
-                // Calcola il percorso con BFS dalla posizione attuale al target
-                self.path = bfs(&self.map, (self.px, self.py), (tx, ty));
-            }
+// Disegna le scale: un rombo (scuro per scendere, chiaro per salire)
+// attraversato dalle linee dei gradini
+fn draw_stairs(x: usize, y: usize, down: bool, light: Color) {
+    let (fill, lines) = if down {
+        (DARKGRAY, BLACK)
+    } else {
+        (LIGHTGRAY, DARKGRAY)
+    };
+    draw_floor_tile(x, y, shade(fill, light));
+    let (sx, sy) = to_screen(x, y);
+    let (top, right) = (vec2(sx, sy), vec2(sx + T_SIZE.0, sy + T_SIZE.1));
+    let (bottom, left) = (
+        vec2(sx, sy + 2. * T_SIZE.1),
+        vec2(sx - T_SIZE.0, sy + T_SIZE.1),
+    );
+    for i in 1..4 {
+        let t = i as f32 / 4.;
+        let (a, b) = (left.lerp(top, t), bottom.lerp(right, t));
+        draw_line(a.x, a.y, b.x, b.y, 2., shade(lines, light));
+    }
+}
+
+// Disegna un oggetto a terra: un piccolo rombo colorato al centro della cella
+fn draw_ground_item(x: usize, y: usize, color: Color) {
+    let (sx, sy) = to_screen(x, y);
+    let (cx, cy) = (sx, sy + T_SIZE.1);
+    let (hw, hh) = (T_SIZE.0 / 3., T_SIZE.1 / 3.);
+    let (top, right) = (vec2(cx, cy - hh), vec2(cx + hw, cy));
+    let (bottom, left) = (vec2(cx, cy + hh), vec2(cx - hw, cy));
+    draw_triangle(top, right, bottom, color);
+    draw_triangle(top, bottom, left, color);
+    for (a, b) in [(top, right), (right, bottom), (bottom, left), (left, top)] {
+        draw_line(a.x, a.y, b.x, b.y, 1., BLACK);
+    }
+}
+
+// Disegna una cella senza altezza: pavimento, macerie, acqua o scale
+fn draw_ground(tile: Tile, x: usize, y: usize, light: Color) {
+    let (sx, sy) = to_screen(x, y);
+    match tile {
+        // Cella pavimento: disegna un piccolo punto grigio
+        Tile::Floor => draw_circle(sx, sy + 16., 2., shade(LIGHTGRAY, light)),
+        // Macerie: sassolini marroni sparsi
+        Tile::Rubble => {
+            let color = shade(Color::new(0.55, 0.45, 0.35, 1.), light);
+            draw_circle(sx - 6., sy + 14., 3., color);
+            draw_circle(sx + 5., sy + 17., 2.5, color);
+            draw_circle(sx, sy + 20., 2., color);
         }
+        // Acqua: rombo azzurro che copre la cella
+        Tile::Water => draw_floor_tile(x, y, shade(SKYBLUE, light)),
+        // Scale verso il piano inferiore e superiore
+        Tile::StairsDown => draw_stairs(x, y, true, light),
+        Tile::StairsUp => draw_stairs(x, y, false, light),
+        // I muri hanno un'altezza: li disegna draw_wall
+        Tile::Wall => {}
+    }
+}
 
-        // Movimento del player lungo il percorso BFS
-        // Usa un cooldown per controllare la velocità (0.15s tra ogni passo)
-        if !self.path.is_empty() {
-            // Decrementa il cooldown in base al delta time (tempo tra frame)
-            self.player_cd -= dt;
+// Disegna un proiettile in volo: una pallina all'altezza del petto
+// con una breve scia nella direzione opposta al movimento.
+// Il colore è quello del mostro o dell'abilità che l'ha lanciato
+fn draw_projectile(game: &Game, p: &Projectile) {
+    let c = match p.source {
+        Source::Monster(kind) => game.kinds[kind].color,
+        Source::Player(skill) => game.skills[skill].color,
+    };
+    let color = Color::new(c.0, c.1, c.2, 1.);
+    let (sx, sy) = to_screen_f(p.pos.0, p.pos.1);
+    let (tx, ty) = to_screen_f(p.pos.0 - p.dir.0 * 0.5, p.pos.1 - p.dir.1 * 0.5);
+    draw_line(tx, ty - 4., sx, sy - 4., 2., color);
+    draw_circle(sx, sy - 4., 3., color);
+}
 
-            // Quando il cooldown arriva a 0, è ora di muoversi
-            if self.player_cd <= 0. {
-                // Reset del cooldown per il prossimo passo
-                self.player_cd = 0.15;
+// Un elemento della coda di disegno.
+// Nella vista isometrica una cella con x + y maggiore sta più in basso sullo
+// schermo, cioè più vicina a chi guarda: va disegnata dopo, così copre quello
+// che le sta dietro. Il "layer" decide l'ordine tra elementi alla stessa profondità
+enum Piece<'a> {
+    Ground(usize, usize, Color), // Pavimento, macerie, acqua, scale
+    PathDot(usize, usize),       // Cella del percorso A*
+    Hover(usize, usize, Cursor), // Cella sotto il mouse
+    TargetRing((f32, f32)),      // Cerchio sotto il mostro bloccato
+    Item(usize, usize, Color),   // Oggetto a terra (già colorato dalla luce)
+    Wall(usize, usize, Color),
+    Torch(&'a Torch, Color),
+    Chest((usize, usize), Color),
+    Actor((f32, f32), Figure<'a>, &'a Effects, Color, Pose), // Player, mostro o NPC
+    Projectile(&'a Projectile),
+}
 
-                // Prossima cella nel percorso
-                let (nx, ny) = self.path[0];
+impl Piece<'_> {
+    // Ordine alla stessa profondità: prima ciò che è piatto a terra,
+    // poi muri e torce, infine personaggi e proiettili
+    fn layer(&self) -> u8 {
+        match self {
+            Piece::Ground(..) => 0,
+            Piece::PathDot(..) | Piece::Hover(..) | Piece::TargetRing(..) => 1,
+            Piece::Item(..) => 2,
+            Piece::Wall(..) => 3,
+            Piece::Torch(..) => 4,
+            Piece::Chest(..) | Piece::Actor(..) => 5,
+            Piece::Projectile(..) => 6,
+        }
+    }
+}
 
-                // Logica di combattimento: controlla se c'è un mostro nella prossima cella
-                // iter().position() cerca l'indice del primo mostro che occupa (nx, ny)
-                if let Some(i) = self.monsters.iter().position(|m| m.x == nx && m.y == ny) {
-                    // Mostro trovato! Attacca invece di muoversi
-                    self.damage_monster(i, 10);
-                    // Ferma il movimento (il player deve cliccare di nuovo)
-                    self.path.clear();
-                } else {
-                    // Nessun mostro: muovi il player nella cella
-                    self.path.remove(0);
-                    self.px = nx;
-                    self.py = ny;
+// true se il muro in `cell` copre il player: sta davanti a lui (più in basso
+// sullo schermo) e il suo cubo si sovrappone allo stickman
+fn hides_player(game: &Game, cell: (usize, usize), alpha: f32) -> bool {
+    let (px, py) = game.player_draw_pos(alpha);
+    if (cell.0 + cell.1) as f32 <= px + py {
+        return false;
+    }
+    // Riquadri nel mondo: cubo del muro e stickman
+    let (wx, wy) = to_screen(cell.0, cell.1);
+    let wall = Rect::new(wx - T_SIZE.0, wy - 40., 2. * T_SIZE.0, 56.);
+    let (sx, sy) = to_screen_f(px, py);
+    let player = Rect::new(sx - 10., sy - 24., 20., 43.);
+    wall.overlaps(&player)
+}
+
+// Disegna tutti gli elementi del gioco sullo schermo
+// camera: cosa inquadrare e con che zoom (appartiene al front end, non alla simulazione)
+// alpha: frazione del prossimo tick già trascorsa, per interpolare i movimenti
+// cutaway: rende trasparenti i muri che nascondono il player
+// hover: cella sotto il mouse, evidenziata con un rombo
+// assets: immagini di celle e personaggi (quelle che mancano sono procedurali)
+fn draw(
+    game: &Game,
+    assets: &Assets,
+    camera: &Camera,
+    alpha: f32,
+    cutaway: bool,
+    hover: Option<(usize, usize)>,
+) {
+    // Fuori dalle zone illuminate è buio
+    clear_background(BACKGROUND);
+    // Il mondo si disegna attraverso la camera, l'HUD direttamente sullo schermo
+    set_camera(&camera.view());
+    let time = get_time() as f32;
+    let lights = light::light_map(&game.map, light::ambient(game.depth), &lights(game, time));
+    let light_at = |cell| tile_light(game, &lights, cell).unwrap_or(REMEMBERED);
+
+    // Coda di disegno: mappa, oggetti, personaggi e proiettili insieme,
+    // ordinati per profondità isometrica (vedi Piece).
+    // Le celle mai viste non si disegnano, quelle ricordate sono più scure,
+    // quelle visibili prendono la luce della loro cella
+    let mut queue: Vec<(usize, Piece)> = vec![];
+    for ((x, y), &tile) in game.map.iter() {
+        let Some(light) = tile_light(game, &lights, (x, y)) else {
+            continue;
+        };
+        if tile == Tile::Wall {
+            // Muro trasparente se copre il player (con cutaway attivo)
+            let light = if cutaway && hides_player(game, (x, y), alpha) {
+                Color { a: 0.3, ..light }
+            } else {
+                light
+            };
+            queue.push((x + y, Piece::Wall(x, y, light)));
+        } else {
+            queue.push((x + y, Piece::Ground(x, y, light)));
+        }
+    }
+    for t in &game.torches {
+        if let Some(light) = tile_light(game, &lights, (t.x, t.y)) {
+            queue.push((t.x + t.y, Piece::Torch(t, light)));
+        }
+    }
+    // Oggetti a terra (quelli nelle celle ricordate restano scuri)
+    for g in &game.ground {
+        if let Some(light) = tile_light(game, &lights, (g.x, g.y)) {
+            let color = shade(item_color(game, &g.item), light);
+            queue.push((g.x + g.y, Piece::Item(g.x, g.y, color)));
+        }
+    }
+    // Percorso calcolato da A*
+    // (mai sulle celle inesplorate: mostrerebbe la strada nel buio)
+    for &(x, y) in &game.path {
+        if game.fog.get((x, y)) != Visibility::Unexplored {
+            queue.push((x + y, Piece::PathDot(x, y)));
+        }
+    }
+    // Cella sotto il mouse
+    if let Some((x, y)) = hover {
+        queue.push((x + y, Piece::Hover(x, y, cursor_for(game, (x, y)))));
+    }
+
+    // Player e mostri nel campo visivo: durante un passo contano la cella di
+    // arrivo e quella di partenza, la più profonda delle due (così il
+    // pavimento di nessuna delle due gli passa sopra)
+    let depth = |cell: (usize, usize), pos: (f32, f32)| {
+        (cell.0 + cell.1).max((pos.0 + pos.1).ceil() as usize)
+    };
+    let pos = game.player_draw_pos(alpha);
+    let light = light_at((game.px, game.py));
+    let pose = Pose::new(game.player_motion(), (game.px, game.py));
+    queue.push((
+        depth((game.px, game.py), pos),
+        Piece::Actor(pos, Figure::Player, &game.effects, light, pose),
+    ));
+    for (i, m) in game.monsters.iter().enumerate() {
+        if !game.fog.visible((m.x, m.y)) {
+            continue;
+        }
+        let pos = m.draw_pos(alpha);
+        let d = depth((m.x, m.y), pos);
+        // Cerchio rosso sotto il mostro bloccato come bersaglio
+        if game.target == Some(Target::Monster(i)) {
+            queue.push((d, Piece::TargetRing(pos)));
+        }
+        let figure = Figure::Monster(&game.kinds[m.kind]);
+        let (light, pose) = (light_at((m.x, m.y)), Pose::new(&m.motion, (m.x, m.y)));
+        queue.push((d, Piece::Actor(pos, figure, &m.effects, light, pose)));
+    }
+    // NPC della città (il forziere è una cassa, non uno stickman)
+    let calm = Effects::default();
+    for npc in game.npcs.iter().filter(|n| game.fog.visible((n.x, n.y))) {
+        let (cell, light) = ((npc.x, npc.y), light_at((npc.x, npc.y)));
+        let piece = if npc.role == Role::Stash {
+            Piece::Chest(cell, light)
+        } else {
+            let pos = (npc.x as f32, npc.y as f32);
+            let figure = Figure::Npc(npc.role);
+            Piece::Actor(pos, figure, &calm, light, Pose::default())
+        };
+        queue.push((npc.x + npc.y, piece));
+    }
+    // Proiettili in volo nel campo visivo
+    for p in &game.projectiles {
+        let cell = nearest_cell(p.pos);
+        if game.fog.visible(cell) {
+            queue.push((cell.0 + cell.1, Piece::Projectile(p)));
+        }
+    }
+
+    // Prima le righe diagonali più lontane (x + y minore), a parità di
+    // profondità prima il pavimento e poi via via ciò che sta più in alto.
+    // sort_by_key è stabile: i pezzi uguali restano nell'ordine di inserimento
+    queue.sort_by_key(|(depth, piece)| (*depth, piece.layer()));
+    for (_, piece) in &queue {
+        match *piece {
+            Piece::Ground(x, y, light) => {
+                let tile = game.map[(x, y)];
+                if !draw_tile_image(assets, atlas::tile_name(tile), x, y, light) {
+                    draw_ground(tile, x, y, light);
+                }
+            }
+            Piece::PathDot(x, y) => {
+                let (sx, sy) = to_screen(x, y);
+                draw_circle(sx, sy + 16., 4., GOLD);
+            }
+            Piece::Hover(x, y, cursor) => {
+                // Bordo del rombo: rosso sui mostri, bianco dove si può andare
+                let color = match cursor {
+                    Cursor::Attack => RED,
+                    Cursor::Walk => WHITE,
+                    Cursor::Blocked => GRAY,
+                };
+                let (sx, sy) = to_screen(x, y);
+                let (top, right) = (vec2(sx, sy), vec2(sx + T_SIZE.0, sy + T_SIZE.1));
+                let (bottom, left) = (
+                    vec2(sx, sy + 2. * T_SIZE.1),
+                    vec2(sx - T_SIZE.0, sy + T_SIZE.1),
+                );
+                for (a, b) in [(top, right), (right, bottom), (bottom, left), (left, top)] {
+                    draw_line(a.x, a.y, b.x, b.y, 2., color);
+                }
+            }
+            Piece::TargetRing((x, y)) => {
+                let (sx, sy) = to_screen_f(x, y);
+                draw_ellipse_lines(sx, sy + T_SIZE.1, 14., 7., 0., 2., RED);
+            }
+            Piece::Item(x, y, color) => draw_ground_item(x, y, color),
+            Piece::Wall(x, y, light) => {
+                if !draw_tile_image(assets, "wall", x, y, light) {
+                    draw_wall(x, y, light);
+                }
+            }
+            Piece::Torch(t, light) => draw_torch(t, light, time),
+            Piece::Chest((x, y), light) => {
+                if !draw_tile_image(assets, "chest", x, y, light) {
+                    draw_chest(x, y, light);
                 }
             }
+            Piece::Actor(pos, figure, effects, light, pose) => {
+                draw_actor(assets, pos, figure, effects, light, pose, time);
+            }
+            Piece::Projectile(p) => draw_projectile(game, p),
         }
+    }
 
-        // Logica dei Mostri
-        //
-        // NOTA SULLE CLOSURE IN RUST:
-        // Una closure è una funzione anonima che può "catturare" variabili dall'ambiente circostante.
-        // Sintassi: |parametri| espressione  oppure  |parametri| { blocco }
-        //
-        // Esempi:
-        //   |x| x * 2           → prende x, ritorna x * 2
-        //   |a, b| a + b        → prende due parametri, ritorna la somma
-        //   |m| (m.x, m.y)      → prende un Monster, ritorna una tupla con le sue coordinate
-        //
-        // Perché si usano:
-        // - Passare logica custom a funzioni come map(), filter(), retain()
-        // - Sono concise: evitano di definire funzioni separate per operazioni semplici
-        // - Possono accedere a variabili locali (es. self.px, self.py nel chain sotto)
-        //
-        // Equivalente JavaScript: (x) => x * 2  oppure  function(x) { return x * 2; }
-        //
-        // Calcola le celle occupate per evitare che i mostri si sovrappongano
-        let occupied: Vec<_> = self
-            .monsters
-            .iter()
-            .map(|m| (m.x, m.y)) // Closure: trasforma ogni Monster in una tupla (x, y)
-            .chain(std::iter::once((self.px, self.py))) // Aggiungi la posizione del player
-            .collect();
-
-        // AI dei mostri: ogni mostro agisce quando il suo cooldown raggiunge 0
-        for i in 0..self.monsters.len() {
-            // Decrementa il cooldown del mostro
-            self.monsters[i].cd -= dt;
-
-            // Quando il cooldown arriva a 0, il mostro può agire
-            if self.monsters[i].cd <= 0. {
-                // Reset cooldown: il mostro agirà di nuovo tra 1 secondo
-                self.monsters[i].cd = 1.0;
-
-                let (mx, my) = (self.monsters[i].x, self.monsters[i].y);
-
-                // Calcola la distanza Manhattan dal player
-                let d = dist((mx, my), (self.px, self.py));
-
-                if d == 1 {
-                    // Mostro adiacente al player (distanza 1): ATTACCA!
-                    self.hp -= 5;
-                    // Mostra il danno subito dal player
-                    let (sx, sy) = to_screen(self.px, self.py, self.cam);
-                    self.texts.push(DmgText {
-                        x: sx,
-                        y: sy,
-                        dmg: 5,
-                        life: 1.,
-                    });
-                } else {
-                    // Mostro lontano: INSEGUI il player usando BFS
-                    let path = bfs(&self.map, (mx, my), (self.px, self.py));
-                    // Muovi solo se c'è un percorso e la cella non è occupata
-                    if path.len() > 1 && !occupied.contains(&path[0]) {
-                        self.monsters[i].x = path[0].0;
-                        self.monsters[i].y = path[0].1;
-                    }
-                }
+    // Nomi degli NPC sopra la testa, sopra a tutto il resto
+    for npc in game.npcs.iter().filter(|n| game.fog.visible((n.x, n.y))) {
+        let (sx, sy) = to_screen(npc.x, npc.y);
+        let name = npc.role.name();
+        let w = measure_text(name, None, 16, 1.).width;
+        draw_text(name, sx - w / 2., sy - 30., 16., LIGHTGRAY);
+    }
+
+    // Disegna le esplosioni delle abilità: un anello sul pavimento che si
+    // allarga fino al raggio dell'effetto e intanto sbiadisce.
+    // Un cerchio sulla griglia diventa un'ellisse 2:1 nella vista isometrica
+    for b in &game.blasts {
+        let c = game.skills[b.skill].color;
+        let t = 1. - b.life / BLAST_TIME;
+        let (sx, sy) = to_screen_f(b.pos.0, b.pos.1);
+        let r = b.radius * t * std::f32::consts::SQRT_2;
+        draw_ellipse_lines(
+            sx,
+            sy + T_SIZE.1,
+            r * T_SIZE.0,
+            r * T_SIZE.1,
+            0.,
+            3.,
+            Color::new(c.0, c.1, c.2, 1. - t),
+        );
+    }
+
+    // Disegna i testi di danno fluttuanti (es. "-10" in rosso che sale):
+    // colore per tipo di danno, più grandi i critici, grigi schivate e parate
+    for t in game.texts.iter().filter(|t| game.fog.visible((t.x, t.y))) {
+        let (sx, sy) = to_screen(t.x, t.y);
+        let (text, size, color) = match t.outcome {
+            Outcome::Hit => (format!("-{}", t.dmg), 20., damage_color(t.kind)),
+            Outcome::Crit => (format!("CRIT -{}", t.dmg), 28., damage_color(t.kind)),
+            Outcome::Miss => ("MISS".to_string(), 18., GRAY),
+            Outcome::Block => ("BLOCK".to_string(), 18., LIGHTGRAY),
+        };
+        draw_text(&text, sx, sy - t.rise, size, color);
+    }
+
+    set_default_camera();
+
+    // HUD (Head-Up Display): mostra le statistiche del player
+    // Posizionato in basso a sinistra dello schermo, in chiaro sullo sfondo scuro
+    let c = &game.character;
+    draw_text(
+        &format!("HP: {}/{}", game.hp, c.max_hp()),
+        20.,
+        screen_height() - 40.,
+        30.,
+        WHITE,
+    );
+    draw_text(
+        &format!("Mana: {}/{}", game.mana, c.max_mana()),
+        20.,
+        screen_height() - 15.,
+        22.,
+        SKYBLUE,
+    );
+    // Icone degli effetti di stato sul player, sopra gli HP:
+    // un quadrato colorato con l'iniziale e i secondi rimanenti
+    let active = StatusKind::ALL.into_iter().filter(|&k| game.effects.has(k));
+    for (i, kind) in active.enumerate() {
+        let (x, y) = (20. + i as f32 * 60., screen_height() - 90.);
+        draw_rectangle(x, y, 20., 20., status_color(kind));
+        let letter = kind.name()[..1].to_uppercase();
+        draw_text(&letter, x + 5., y + 16., 20., BLACK);
+        let time = format!("{:.0}s", game.effects.remaining(kind).ceil());
+        draw_text(&time, x + 24., y + 16., 18., LIGHTGRAY);
+    }
+
+    // Piano del dungeon in alto a destra
+    let floor = match game.depth {
+        0 => "Town".to_string(),
+        d => format!("Floor {d}"),
+    };
+    draw_text(&floor, screen_width() - 110., 30., 26., WHITE);
+
+    // Livello ed esperienza in basso a destra
+    draw_text(
+        &format!("Level {}  XP {}/{}", c.level, c.xp, c.next_level_xp()),
+        screen_width() - 200.,
+        screen_height() - 15.,
+        22.,
+        WHITE,
+    );
+    if c.points > 0 {
+        draw_text(
+            "Level up! C: character",
+            screen_width() - 200.,
+            screen_height() - 40.,
+            22.,
+            GREEN,
+        );
+    }
+}
+
+// Casella i-esima della barra delle abilità, centrata in basso
+fn hotbar_slot(i: usize) -> Rect {
+    let size = 40.;
+    let left = screen_width() / 2. - HOTBAR_SIZE as f32 * size / 2.;
+    Rect::new(
+        left + i as f32 * size,
+        screen_height() - size - 6.,
+        size - 4.,
+        size - 4.,
+    )
+}
+
+// Disegna la barra delle abilità: numero del tasto, nome abbreviato,
+// ricarica (la casella si riempie di grigio) e abilità selezionata (bordo dorato).
+// Il nome è rosso se non c'è abbastanza mana
+fn draw_hotbar(game: &Game, selected: usize) {
+    for i in 0..HOTBAR_SIZE {
+        let r = hotbar_slot(i);
+        draw_rectangle(r.x, r.y, r.w, r.h, Color::new(1., 1., 1., 0.9));
+        if let Some(skill) = game.skills.get(i) {
+            let c = skill.color;
+            draw_rectangle(
+                r.x + 4.,
+                r.y + 4.,
+                r.w - 8.,
+                6.,
+                Color::new(c.0, c.1, c.2, 1.),
+            );
+            let name: String = skill.name.chars().take(5).collect();
+            let color = if game.mana < skill.mana { RED } else { BLACK };
+            draw_text(&name, r.x + 3., r.y + 30., 15., color);
+            if skill.cooldown > 0. && game.skill_cd[i] > 0. {
+                let h = r.h * game.skill_cd[i] / skill.cooldown;
+                draw_rectangle(r.x, r.y + r.h - h, r.w, h, Color::new(0.3, 0.3, 0.3, 0.6));
             }
         }
+        draw_text(
+            &(i + 1).to_string(),
+            r.x + 3.,
+            r.y + r.h - 2.,
+            14.,
+            DARKGRAY,
+        );
+        let (width, color) = if i == selected {
+            (3., GOLD)
+        } else {
+            (1., BLACK)
+        };
+        draw_rectangle_lines(r.x, r.y, r.w, r.h, width, color);
+    }
+}
+
+// Colore di un oggetto (dal suo tipo base)
+fn item_color(game: &Game, item: &Item) -> Color {
+    let c = game.item_data.bases[item.base].color;
+    Color::new(c.0, c.1, c.2, 1.)
+}
+
+// Lato in pixel di una cella dell'inventario
+const INV_CELL: f32 = 30.;
 
-        false
+// Angolo in alto a sinistra della griglia dell'inventario (a destra dello schermo,
+// sotto la riga degli slot dell'equipaggiamento)
+fn inventory_origin() -> Vec2 {
+    vec2(screen_width() - 20. - INV_SIZE.0 as f32 * INV_CELL, 170.)
+}
+
+// Riquadro dell'inventario, titolo ed equipaggiamento compresi
+fn inventory_panel() -> Rect {
+    let o = inventory_origin();
+    Rect::new(
+        o.x - 10.,
+        o.y - 110.,
+        INV_SIZE.0 as f32 * INV_CELL + 20.,
+        INV_SIZE.1 as f32 * INV_CELL + 120.,
+    )
+}
+
+// Riquadro dello slot di equipaggiamento della categoria
+fn slot_rect(class: ItemClass) -> Rect {
+    let o = inventory_origin();
+    Rect::new(o.x + class.slot() as f32 * 60. + 5., o.y - 75., 50., 50.)
+}
+
+// Slot di equipaggiamento sotto il punto dello schermo
+fn slot_at(p: Vec2) -> Option<ItemClass> {
+    ItemClass::ALL
+        .into_iter()
+        .find(|&c| slot_rect(c).contains(p))
+}
+
+// Colore del nome di un oggetto secondo la rarità (come in Diablo)
+fn rarity_color(rarity: Rarity) -> Color {
+    match rarity {
+        Rarity::Normal => BLACK,
+        Rarity::Magic => BLUE,
+        Rarity::Rare => Color::new(0.8, 0.65, 0., 1.),
+        Rarity::Unique => Color::new(0.6, 0.4, 0.1, 1.),
     }
+}
 
-    // Infligge danno a un mostro e gestisce la sua morte
-    // idx: indice del mostro nel vettore monsters
-    // amount: quantità di danno da infliggere
-    fn damage_monster(&mut self, idx: usize, amount: i32) {
-        // Sottrai HP al mostro
-        self.monsters[idx].hp -= amount;
+// Cella di una griglia INV_SIZE con l'angolo in `origin` sotto il punto dello
+// schermo (None se fuori dalla griglia). Serve per inventario e forziere
+fn grid_cell(p: Vec2, origin: Vec2) -> Option<(usize, usize)> {
+    let d = (p - origin) / INV_CELL;
+    let inside = d.x >= 0. && d.y >= 0. && d.x < INV_SIZE.0 as f32 && d.y < INV_SIZE.1 as f32;
+    inside.then_some((d.x as usize, d.y as usize))
+}
 
-        // Crea il testo fluttuante del danno sopra il mostro
-        let (sx, sy) = to_screen(self.monsters[idx].x, self.monsters[idx].y, self.cam);
-        self.texts.push(DmgText {
-            x: sx,
-            y: sy - 40., // Parte sopra la testa del mostro
-            dmg: amount,
-            life: 1., // Dura 1 secondo
-        });
+// Cella dell'inventario sotto il punto dello schermo (None se fuori dalla griglia)
+fn inventory_cell(p: Vec2) -> Option<(usize, usize)> {
+    grid_cell(p, inventory_origin())
+}
 
-        // Se HP <= 0, il mostro muore: rimuovilo dal vettore
-        if self.monsters[idx].hp <= 0 {
-            self.monsters.remove(idx);
+// Disegna le righe di una griglia INV_SIZE con l'angolo in `origin`
+fn draw_grid(origin: Vec2) {
+    for x in 0..INV_SIZE.0 {
+        for y in 0..INV_SIZE.1 {
+            let (cx, cy) = (
+                origin.x + x as f32 * INV_CELL,
+                origin.y + y as f32 * INV_CELL,
+            );
+            draw_rectangle_lines(cx, cy, INV_CELL, INV_CELL, 1., LIGHTGRAY);
         }
     }
+}
 
-    // Disegna tutti gli elementi del gioco sullo schermo
-    fn draw(&self) {
-        // Disegna la mappa: itera su tutte le celle della griglia
-        // L'ordine (y poi x) garantisce il corretto z-ordering isometrico
-        for y in 0..MAP {
-            for x in 0..MAP {
-                if self.map[y][x] == Tile::Wall {
-                    // Cella muro: disegna cubo 3D
-                    draw_wall(x, y, self.cam);
-                } else {
-                    // Cella pavimento: disegna un piccolo punto grigio
-                    let (sx, sy) = to_screen(x, y, self.cam);
-                    draw_circle(sx, sy + 16., 2., LIGHTGRAY);
-                }
+// Disegna un oggetto come rettangolo del suo colore, bordato con il colore della rarità
+fn draw_item_box(game: &Game, item: &Item, pos: Vec2, w: f32, h: f32) {
+    draw_rectangle(
+        pos.x + 1.,
+        pos.y + 1.,
+        w - 2.,
+        h - 2.,
+        item_color(game, item),
+    );
+    draw_rectangle_lines(
+        pos.x + 1.,
+        pos.y + 1.,
+        w - 2.,
+        h - 2.,
+        2.,
+        rarity_color(item.rarity),
+    );
+}
+
+// Oggetto trascinato con il mouse nell'inventario (solo front end: lo
+// spostamento vero arriva alla simulazione come comando al rilascio)
+struct Drag {
+    cell: (usize, usize),   // Cella dell'inventario da cui è stato preso
+    offset: (usize, usize), // Cella presa rispetto all'angolo dell'oggetto
+}
+
+// Disegna l'inventario a griglia; l'oggetto trascinato segue il mouse e
+// sotto di lui si evidenzia dove finirebbe (verde = c'è posto, rosso = no)
+fn draw_inventory(game: &Game, drag: Option<&Drag>) {
+    let r = inventory_panel();
+    let o = inventory_origin();
+    draw_rectangle(r.x, r.y, r.w, r.h, Color::new(1., 1., 1., 0.9));
+    draw_rectangle_lines(r.x, r.y, r.w, r.h, 2., BLACK);
+    draw_text("Inventory", r.x + 10., r.y + 20., 22., BLACK);
+    draw_text(
+        &format!("Gold: {}", game.gold),
+        r.x + r.w - 100.,
+        r.y + 20.,
+        20.,
+        Color::new(0.7, 0.55, 0., 1.),
+    );
+
+    // Slot dell'equipaggiamento: l'oggetto indossato o il nome dello slot
+    for class in ItemClass::ALL {
+        let s = slot_rect(class);
+        match game.equipment.get(class) {
+            Some(item) => {
+                draw_rectangle(s.x, s.y, s.w, s.h, item_color(game, item));
+                draw_rectangle_lines(s.x, s.y, s.w, s.h, 2., rarity_color(item.rarity));
+            }
+            None => {
+                draw_rectangle_lines(s.x, s.y, s.w, s.h, 1., GRAY);
+                draw_text(class.name(), s.x + 3., s.y + 30., 14., GRAY);
             }
         }
+    }
+    draw_grid(o);
 
-        // Disegna il percorso calcolato da BFS come cerchi dorati
-        for (px, py) in &self.path {
-            let (sx, sy) = to_screen(*px, *py, self.cam);
-            draw_circle(sx, sy + 16., 4., GOLD);
+    let dragged = drag.and_then(|d| game.inventory.at(d.cell));
+    for (i, s) in game.inventory.items.iter().enumerate() {
+        let (w, h) = (s.size.0 as f32 * INV_CELL, s.size.1 as f32 * INV_CELL);
+        let mut pos = vec2(
+            o.x + s.pos.0 as f32 * INV_CELL,
+            o.y + s.pos.1 as f32 * INV_CELL,
+        );
+        if Some(i) == dragged
+            && let Some(d) = drag
+        {
+            // Bersaglio sotto il mouse
+            let mouse = Vec2::from(mouse_position());
+            if let Some(to) = inventory_cell(mouse).and_then(|c| drop_target(c, d)) {
+                let ok = game.inventory.fits(to, s.size, Some(i));
+                let color = if ok { GREEN } else { RED };
+                draw_rectangle(
+                    o.x + to.0 as f32 * INV_CELL,
+                    o.y + to.1 as f32 * INV_CELL,
+                    w,
+                    h,
+                    Color::new(color.r, color.g, color.b, 0.3),
+                );
+            }
+            // L'oggetto segue il mouse
+            pos = mouse - vec2(d.offset.0 as f32 + 0.5, d.offset.1 as f32 + 0.5) * INV_CELL;
         }
+        draw_item_box(game, &s.item, pos, w, h);
+    }
 
-        // Disegna il player (enemy=false → testa tonda)
-        draw_stickman(self.px, self.py, self.cam, false);
-
-        // Disegna tutti i mostri (enemy=true → corna)
-        for m in &self.monsters {
-            draw_stickman(m.x, m.y, self.cam, true);
+    // Tooltip dell'oggetto sotto il mouse (se non se ne sta trascinando uno)
+    let mouse = Vec2::from(mouse_position());
+    if drag.is_none() {
+        if let Some(i) = inventory_cell(mouse).and_then(|c| game.inventory.at(c)) {
+            draw_tooltip(game, &game.inventory.items[i].item, true);
+        } else if let Some(item) = slot_at(mouse).and_then(|c| game.equipment.get(c)) {
+            draw_tooltip(game, item, false);
         }
+    }
+}
+
+// Tooltip di un oggetto: nome, valori base e bonus. Con compare=true aggiunge
+// la differenza rispetto all'oggetto indossato nello stesso slot
+fn draw_tooltip(game: &Game, item: &Item, compare: bool) {
+    let base = &game.item_data.bases[item.base];
+    let bonuses = game.item_data.bonuses(item);
+    let mut lines: Vec<(String, Color)> = vec![(item.name.clone(), rarity_color(item.rarity))];
+    if item.name != base.name {
+        lines.push((base.name.clone(), DARKGRAY));
+    }
+    if let Some((lo, hi)) = base.damage {
+        lines.push((format!("Damage: {lo}-{hi}"), BLACK));
+    }
+    if base.defence > 0 {
+        lines.push((format!("Defence: {}", base.defence), BLACK));
+    }
+    for &(bonus, value) in &item.mods {
+        lines.push((format!("+{value} {}", bonus.name()), BLUE));
+    }
 
-        // Disegna i testi di danno fluttuanti (es. "-10" in rosso che sale)
-        for t in &self.texts {
-            draw_text(&format!("-{}", t.dmg), t.x, t.y, 20., RED);
+    // Confronto con l'oggetto indossato: verde = meglio, rosso = peggio
+    if compare && let Some(worn) = game.equipment.get(base.class) {
+        let worn = game.item_data.bonuses(worn);
+        lines.push((
+            format!("Compared to equipped {}:", base.class.name()),
+            DARKGRAY,
+        ));
+        let avg = |d: Option<(i32, i32)>| d.map_or(0, |(lo, hi)| lo + hi) as f32 / 2.;
+        let diff = avg(bonuses.weapon) - avg(worn.weapon);
+        if diff != 0. {
+            let color = if diff > 0. { DARKGREEN } else { RED };
+            lines.push((format!("{diff:+} average damage"), color));
+        }
+        for bonus in Bonus::ALL {
+            let diff = bonuses.get(bonus) - worn.get(bonus);
+            if diff != 0 {
+                let color = if diff > 0 { DARKGREEN } else { RED };
+                lines.push((format!("{diff:+} {}", bonus.name()), color));
+            }
         }
+    }
 
-        // HUD (Head-Up Display): mostra le statistiche del player
-        // Posizionato in basso a sinistra dello schermo
+    // Riquadro a sinistra del mouse (l'inventario è sul lato destro dello schermo)
+    let (mx, my) = mouse_position();
+    let (w, h) = (240., lines.len() as f32 * 20. + 10.);
+    let (x, y) = ((mx - w - 10.).max(0.), my.min(screen_height() - h));
+    draw_rectangle(x, y, w, h, Color::new(1., 1., 0.95, 0.95));
+    draw_rectangle_lines(x, y, w, h, 1., BLACK);
+    for (i, (text, color)) in lines.iter().enumerate() {
+        draw_text(text, x + 8., y + 22. + i as f32 * 20., 18., *color);
+    }
+}
+
+// Angolo in cui finirebbe l'oggetto trascinato rilasciandolo sulla cella `cell`
+fn drop_target(cell: (usize, usize), drag: &Drag) -> Option<(usize, usize)> {
+    Some((
+        cell.0.checked_sub(drag.offset.0)?,
+        cell.1.checked_sub(drag.offset.1)?,
+    ))
+}
+
+// Pulsante "+" della statistica i-esima nella scheda del personaggio
+fn stat_button(i: usize) -> Rect {
+    Rect::new(200., 100. + i as f32 * 25., 18., 18.)
+}
+
+// Riquadro della scheda del personaggio (i click qui dentro non muovono il player)
+fn character_panel() -> Rect {
+    Rect::new(20., 60., 230., 350.)
+}
+
+// Disegna la scheda del personaggio: statistiche, punti da spendere e valori derivati
+fn draw_character(game: &Game) {
+    let c = &game.character;
+    let r = character_panel();
+    draw_rectangle(r.x, r.y, r.w, r.h, Color::new(1., 1., 1., 0.9));
+    draw_rectangle_lines(r.x, r.y, r.w, r.h, 2., BLACK);
+    draw_text(&format!("Level {}", c.level), 30., 85., 24., BLACK);
+
+    for (i, stat) in Stat::ALL.into_iter().enumerate() {
+        let b = stat_button(i);
         draw_text(
-            &format!("HP: {}", self.hp),
-            20.,
-            screen_height() - 40.,
+            &format!("{}: {}", stat.name(), c.stat(stat)),
             30.,
+            b.y + 14.,
+            20.,
             BLACK,
         );
+        // Il "+" compare solo se ci sono punti da spendere
+        if c.points > 0 {
+            draw_rectangle_lines(b.x, b.y, b.w, b.h, 1., DARKGREEN);
+            draw_text("+", b.x + 4., b.y + 14., 20., DARKGREEN);
+        }
+    }
+
+    let (lo, hi) = c.damage();
+    let mut lines = vec![
+        format!("Points: {}", c.points),
+        format!("Damage: {lo}-{hi}"),
+        format!("Action time: {:.2}s", c.action_time()),
+        format!("Defence: {}", c.defence()),
+        format!(
+            "Crit {:.0}%  Dodge {:.0}%  Block {:.0}%",
+            c.crit_chance() * 100.,
+            c.dodge_chance() * 100.,
+            c.block_chance() * 100.
+        ),
+    ];
+    // Resistenze (il danno fisico lo riduce la difesa)
+    let resists = c.resists();
+    for kind in &DamageType::ALL[1..] {
+        lines.push(format!("{} resist: {}%", kind.name(), resists.get(*kind)));
+    }
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(line, 30., 215. + i as f32 * 22., 20., DARKGRAY);
+    }
+}
+
+// Riquadro del dialogo con un NPC (a sinistra, dove sta la scheda del personaggio)
+fn dialog_panel() -> Rect {
+    Rect::new(20., 60., 320., 330.)
+}
+
+// Pulsante "x" che chiude il dialogo
+fn close_button() -> Rect {
+    let r = dialog_panel();
+    Rect::new(r.x + r.w - 26., r.y + 6., 20., 20.)
+}
+
+// Pulsante del guaritore
+fn heal_button() -> Rect {
+    Rect::new(30., 110., 160., 30.)
+}
+
+// Riga i-esima della merce del mercante
+fn stock_row(i: usize) -> Rect {
+    Rect::new(30., 100. + i as f32 * 24., 300., 22.)
+}
+
+// Angolo in alto a sinistra della griglia del forziere
+fn stash_origin() -> Vec2 {
+    vec2(30., 110.)
+}
+
+// Disegna il dialogo con l'NPC aperto: nome, oro del player e cosa offre
+fn draw_dialog(game: &Game, npc: usize) {
+    let role = game.npcs[npc].role;
+    let r = dialog_panel();
+    draw_rectangle(r.x, r.y, r.w, r.h, Color::new(1., 1., 1., 0.9));
+    draw_rectangle_lines(r.x, r.y, r.w, r.h, 2., BLACK);
+    draw_text(role.name(), r.x + 10., r.y + 22., 24., BLACK);
+    draw_text(
+        &format!("Gold: {}", game.gold),
+        r.x + 150.,
+        r.y + 22.,
+        20.,
+        Color::new(0.7, 0.55, 0., 1.),
+    );
+    let b = close_button();
+    draw_rectangle_lines(b.x, b.y, b.w, b.h, 1., BLACK);
+    draw_text("x", b.x + 6., b.y + 15., 20., BLACK);
+
+    let mouse = Vec2::from(mouse_position());
+    let footer = r.y + r.h - 12.;
+    match role {
+        Role::Healer => {
+            let b = heal_button();
+            draw_rectangle_lines(b.x, b.y, b.w, b.h, 2., DARKGREEN);
+            draw_text("Heal (free)", b.x + 10., b.y + 21., 22., DARKGREEN);
+            draw_text(
+                "Restores life and mana, cures effects",
+                30.,
+                170.,
+                18.,
+                DARKGRAY,
+            );
+        }
+        Role::Merchant => {
+            // Una riga per oggetto: nome colorato per rarità e prezzo
+            // (rosso se non bastano i soldi)
+            let stock = &game.npcs[npc].stock;
+            for (i, item) in stock.iter().enumerate() {
+                let row = stock_row(i);
+                if row.contains(mouse) {
+                    draw_rectangle(row.x, row.y, row.w, row.h, Color::new(0.9, 0.9, 0.8, 1.));
+                }
+                draw_rectangle(row.x + 2., row.y + 4., 14., 14., item_color(game, item));
+                draw_text(
+                    &item.name,
+                    row.x + 22.,
+                    row.y + 16.,
+                    18.,
+                    rarity_color(item.rarity),
+                );
+                let price = game.item_data.price(item);
+                let color = if price > game.gold { RED } else { BLACK };
+                draw_text(&price.to_string(), row.x + 250., row.y + 16., 18., color);
+            }
+            draw_text(
+                "Click to buy, right-click your items to sell",
+                30.,
+                footer,
+                16.,
+                DARKGRAY,
+            );
+            if let Some(item) = (0..stock.len())
+                .find(|&i| stock_row(i).contains(mouse))
+                .map(|i| &stock[i])
+            {
+                draw_tooltip(game, item, true);
+            }
+        }
+        Role::Stash => {
+            let o = stash_origin();
+            draw_grid(o);
+            for s in &game.stash.items {
+                let pos = o + vec2(s.pos.0 as f32, s.pos.1 as f32) * INV_CELL;
+                let (w, h) = (s.size.0 as f32 * INV_CELL, s.size.1 as f32 * INV_CELL);
+                draw_item_box(game, &s.item, pos, w, h);
+            }
+            draw_text(
+                "Click to take, right-click your items to store",
+                30.,
+                footer,
+                16.,
+                DARKGRAY,
+            );
+            if let Some(i) = grid_cell(mouse, o).and_then(|c| game.stash.at(c)) {
+                draw_tooltip(game, &game.stash.items[i].item, true);
+            }
+        }
+    }
+}
+
+// Seed per il generatore: usa l'orologio di sistema, così ogni partita è diversa
+fn seed() -> u64 {
+    (miniquad::date::now() * 1000.) as u64
+}
+
+// Zoom minimo e massimo della camera (1 = dimensioni normali)
+const ZOOM_RANGE: (f32, f32) = (0.5, 2.5);
+// Velocità con cui la camera raggiunge il player (più alta = più rigida)
+const FOLLOW_SPEED: f32 = 6.;
+// Durata del tremolio dello schermo quando il player viene colpito (secondi)
+// e spostamento massimo in pixel
+const SHAKE_TIME: f32 = 0.25;
+const SHAKE_PIXELS: f32 = 6.;
+
+// Camera che segue il player (solo front end).
+// Lavora in coordinate del mondo (quelle di to_screen): macroquad poi scala
+// e sposta tutto il disegno, quindi lo zoom ingrandisce muri, personaggi e testi
+// senza toccare le funzioni di disegno
+struct Camera {
+    pos: Vec2,      // Punto del mondo al centro dello schermo
+    zoom: f32,      // Ingrandimento
+    shake: f32,     // Secondi di tremolio rimanenti
+    last_hits: u32, // Colpi subiti dal player al frame prima (vedi Game::hits)
+    depth: u32,     // Piano inquadrato: cambiando piano la camera salta sul player
+}
+
+impl Camera {
+    // Camera centrata sul player della partita
+    fn new(game: &Game) -> Self {
+        let (x, y) = to_screen_f(game.px as f32, game.py as f32);
+        let mut camera = Camera {
+            pos: vec2(x, y),
+            zoom: 1.,
+            shake: 0.,
+            last_hits: game.hits,
+            depth: game.depth,
+        };
+        camera.clamp(game);
+        camera
+    }
+
+    // Avanza di dt secondi: si avvicina al player (di una frazione della
+    // distanza che dipende da dt, così la velocità non cambia con gli FPS),
+    // e trema se il player ha appena preso un colpo (non per veleno o fuoco)
+    fn update(&mut self, game: &Game, alpha: f32, dt: f32) {
+        let (x, y) = game.player_draw_pos(alpha);
+        let (tx, ty) = to_screen_f(x, y);
+        let t = if game.depth == self.depth {
+            1. - (-FOLLOW_SPEED * dt).exp()
+        } else {
+            1.
+        };
+        self.depth = game.depth;
+        self.pos += (vec2(tx, ty) - self.pos) * t;
+        self.clamp(game);
+
+        if game.hits > self.last_hits {
+            self.shake = SHAKE_TIME;
+        }
+        self.last_hits = game.hits;
+        self.shake = (self.shake - dt).max(0.);
+    }
+
+    // Rotella del mouse: ogni scatto ingrandisce o rimpicciolisce del 10%
+    fn zoom_by(&mut self, wheel: f32, game: &Game) {
+        if wheel != 0. {
+            let step = if wheel > 0. { 1.1 } else { 1. / 1.1 };
+            self.zoom = (self.zoom * step).clamp(ZOOM_RANGE.0, ZOOM_RANGE.1);
+            self.clamp(game);
+        }
+    }
+
+    // Tiene l'inquadratura dentro la mappa; se la mappa è più piccola dello
+    // schermo su un asse, la centra su quell'asse
+    fn clamp(&mut self, game: &Game) {
+        let (w, h) = (game.map.width(), game.map.height());
+        // Riquadro della mappa nel mondo: angoli sinistro, destro, alto e basso
+        // del rombo, più l'altezza dei muri in alto
+        let left = to_screen(0, h - 1).0 - T_SIZE.0;
+        let right = to_screen(w - 1, 0).0 + T_SIZE.0;
+        let top = to_screen(0, 0).1 - 40.;
+        let bottom = to_screen(w - 1, h - 1).1 + 2. * T_SIZE.1;
+        let half = vec2(screen_width(), screen_height()) / (2. * self.zoom);
+        let axis = |pos: f32, lo: f32, hi: f32, half: f32| {
+            if hi - lo <= 2. * half {
+                (lo + hi) / 2.
+            } else {
+                pos.clamp(lo + half, hi - half)
+            }
+        };
+        self.pos.x = axis(self.pos.x, left, right, half.x);
+        self.pos.y = axis(self.pos.y, top, bottom, half.y);
+    }
+
+    // Camera di macroquad per disegnare il mondo: inquadra pos con lo zoom,
+    // spostata dal tremolio (una vibrazione veloce che si smorza)
+    fn view(&self) -> Camera2D {
+        let t = get_time() as f32;
+        let power = self.shake / SHAKE_TIME * SHAKE_PIXELS;
+        let shake = vec2((t * 90.).sin(), (t * 77.).cos()) * power;
+        Camera2D {
+            target: self.pos + shake,
+            zoom: vec2(
+                2. * self.zoom / screen_width(),
+                2. * self.zoom / screen_height(),
+            ),
+            ..Default::default()
+        }
     }
+
+    // Punto del mondo sotto un punto dello schermo (es. il mouse)
+    fn to_world(&self, p: Vec2) -> Vec2 {
+        self.pos + (p - vec2(screen_width(), screen_height()) / 2.) / self.zoom
+    }
+}
+
+// Stato del visualizzatore di replay (solo front end)
+struct ReplayView {
+    playback: Playback,
+    paused: bool,
+    speed: f32, // Moltiplicatore di velocità (da 0.25x a 8x)
 }
 
 // Entry point del gioco - macroquad gestisce il window e il game loop
 #[macroquad::main("Crablo")]
 async fn main() {
-    let mut game = Game::new();
+    let mut game = Game::new(seed(), GenMode::Rooms, MAP_SIZE);
+    // Immagini facoltative di celle e personaggi
+    let assets = Assets::load(ASSETS_DIR).await;
     let mut state = AppState::Menu;
+    // Camera che segue il player
+    let mut camera = Camera::new(&game);
+    // Tempo reale accumulato non ancora simulato (sempre < TICK dopo l'update)
+    let mut acc = 0.;
+    // Comandi in attesa del prossimo tick
+    let mut commands = vec![];
+    // Registrazione della partita in corso (comandi + tick).
+    // None per le partite caricate da file: il replay riparte sempre dal seed,
+    // quindi non può riprodurre una partita iniziata da un salvataggio.
+    let mut recording = Some(Replay::new(&game));
+    // true se c'è una partita in corso da riprendere o salvare dal menu
+    let mut in_progress = false;
+    // Visualizzatore attivo quando lo stato è AppState::Replay
+    let mut view: Option<ReplayView> = None;
+    // Messaggio mostrato nel menu (es. errore di caricamento del replay)
+    let mut message = String::new();
+    // Scheda del personaggio aperta (tasto C durante la partita)
+    let mut show_character = false;
+    // Inventario aperto (tasto I) e oggetto trascinato
+    let mut show_inventory = false;
+    let mut drag: Option<Drag> = None;
+    // Abilità selezionata nella barra (tasti 1-8), lanciata con il click destro
+    let mut selected = 0;
+    // Cella verso cui si sta attaccando sul posto (Shift + tasto sinistro tenuto premuto)
+    let mut in_place: Option<(usize, usize)> = None;
+    // Muri trasparenti quando coprono il player (tasto T)
+    let mut cutaway = true;
+    // true finché si vede il puntatore del sistema (sulla mappa si usa quello del gioco)
+    let mut system_cursor = true;
+
+    // Intercetta la chiusura della finestra per salvare automaticamente
+    prevent_quit();
 
     // Game loop principale: gira finché la finestra è aperta
     loop {
+        // Chiusura della finestra: salva la partita in corso ed esci
+        if is_quit_requested() {
+            if in_progress && let Err(e) = save::save(&game, SAVE_FILE, SaveFormat::Ron) {
+                eprintln!("Cannot save {SAVE_FILE}: {e}");
+            }
+            break;
+        }
+
         // Pulisce lo schermo con sfondo bianco
         clear_background(WHITE);
+        // Puntatore del gioco da disegnare in questo frame (solo sulla mappa)
+        let mut map_cursor = None;
 
         // State machine: gestisce i diversi stati del gioco
         match state {
             // Schermata menu iniziale
             AppState::Menu => {
                 draw_text("Menu - Enter to start", 100., 100., 40., BLACK);
-                if is_key_pressed(KeyCode::Enter) {
-                    // Crea nuova partita e passa allo stato Playing
-                    game = Game::new();
+                draw_text(
+                    "C: caves   L: load   R: watch last replay",
+                    100.,
+                    140.,
+                    20.,
+                    GRAY,
+                );
+                if in_progress {
+                    draw_text(
+                        "Esc: resume   S: save   B: save (binary)",
+                        100.,
+                        165.,
+                        20.,
+                        GRAY,
+                    );
+                }
+                draw_text(&message, 100., 200., 20., RED);
+
+                // Crea nuova partita (con un seed nuovo) e passa allo stato Playing
+                let mode = if is_key_pressed(KeyCode::Enter) {
+                    Some(GenMode::Rooms)
+                } else if is_key_pressed(KeyCode::C) {
+                    Some(GenMode::Caves)
+                } else {
+                    None
+                };
+                if let Some(mode) = mode {
+                    game = Game::new(seed(), mode, MAP_SIZE);
+                    recording = Some(Replay::new(&game));
+                    camera = Camera::new(&game);
+                    acc = 0.;
+                    in_progress = true;
+                    message.clear();
+                    state = AppState::Playing;
+                }
+
+                // Salvataggio della partita in corso (S: RON leggibile, B: binario compatto)
+                let format = if is_key_pressed(KeyCode::S) {
+                    Some(SaveFormat::Ron)
+                } else if is_key_pressed(KeyCode::B) {
+                    Some(SaveFormat::Binary)
+                } else {
+                    None
+                };
+                if let Some(format) = format
+                    && in_progress
+                {
+                    message = match save::save(&game, SAVE_FILE, format) {
+                        Ok(()) => format!("Game saved to {SAVE_FILE}"),
+                        Err(e) => format!("Cannot save {SAVE_FILE}: {e}"),
+                    };
+                }
+
+                // Caricamento: in caso di errore la partita attuale resta com'è
+                if is_key_pressed(KeyCode::L) {
+                    match save::load(SAVE_FILE) {
+                        Ok(loaded) => {
+                            game = loaded;
+                            recording = None;
+                            camera = Camera::new(&game);
+                            acc = 0.;
+                            in_progress = true;
+                            message.clear();
+                            state = AppState::Playing;
+                        }
+                        Err(e) => message = format!("Cannot load {SAVE_FILE}: {e}"),
+                    }
+                }
+
+                // Esc: torna alla partita in corso
+                if is_key_pressed(KeyCode::Escape) && in_progress {
+                    message.clear();
                     state = AppState::Playing;
                 }
+
+                // Carica l'ultimo replay salvato e passa al visualizzatore
+                if is_key_pressed(KeyCode::R) {
+                    match Replay::load(REPLAY_FILE) {
+                        Ok(replay) => {
+                            game = replay.start();
+                            view = Some(ReplayView {
+                                playback: Playback::new(replay),
+                                paused: false,
+                                speed: 1.,
+                            });
+                            camera = Camera::new(&game);
+                            acc = 0.;
+                            in_progress = false;
+                            message.clear();
+                            state = AppState::Replay;
+                        }
+                        Err(e) => message = format!("Cannot load {REPLAY_FILE}: {e}"),
+                    }
+                }
             }
 
             // Gioco in corso
             AppState::Playing => {
-                // get_frame_time() ritorna il delta time per movimento fluido
-                if game.update(get_frame_time()) {
-                    // Se update() ritorna true, passa a GameOver
-                    state = AppState::GameOver;
+                if is_key_pressed(KeyCode::C) {
+                    show_character = !show_character;
+                }
+                if is_key_pressed(KeyCode::I) {
+                    show_inventory = !show_inventory;
+                    drag = None;
+                }
+                if is_key_pressed(KeyCode::T) {
+                    cutaway = !cutaway;
+                }
+                // Rotella del mouse: zoom della camera
+                camera.zoom_by(mouse_wheel().1, &game);
+                // Tasti 1-8: seleziona l'abilità della casella corrispondente
+                let keys = [
+                    KeyCode::Key1,
+                    KeyCode::Key2,
+                    KeyCode::Key3,
+                    KeyCode::Key4,
+                    KeyCode::Key5,
+                    KeyCode::Key6,
+                    KeyCode::Key7,
+                    KeyCode::Key8,
+                ];
+                if let Some(i) = keys.iter().position(|&k| is_key_pressed(k)) {
+                    selected = i;
+                }
+
+                // Con il mercante o il forziere l'inventario si apre da solo
+                let trading = game
+                    .dialog
+                    .is_some_and(|i| game.npcs[i].role != Role::Healer);
+                let inventory_open = show_inventory || trading;
+                if !inventory_open {
+                    drag = None;
+                }
+
+                // Cosa c'è sotto il mouse: un pannello dell'interfaccia oppure
+                // una cella della mappa (None fuori dalla mappa)
+                let mouse = Vec2::from(mouse_position());
+                let over_ui = (0..HOTBAR_SIZE).any(|i| hotbar_slot(i).contains(mouse))
+                    || (game.dialog.is_some() && dialog_panel().contains(mouse))
+                    || (show_character && character_panel().contains(mouse))
+                    || (inventory_open && inventory_panel().contains(mouse));
+                let hover = if over_ui {
+                    None
+                } else {
+                    pick(&game, camera.to_world(mouse), acc / TICK)
+                };
+
+                // Input mouse: al click sinistro, vai verso la cella cliccata
+                // (o spendi un punto, se il click è su un "+" della scheda,
+                // o prendi un oggetto, se il click è nell'inventario,
+                // o seleziona un'abilità, se il click è sulla barra)
+                if is_mouse_button_pressed(MouseButton::Left) {
+                    let (mx, my) = mouse_position();
+                    let mouse = vec2(mx, my);
+                    if let Some(i) = (0..HOTBAR_SIZE).find(|&i| hotbar_slot(i).contains(mouse)) {
+                        selected = i;
+                    } else if let Some(npc) = game.dialog
+                        && dialog_panel().contains(mouse)
+                    {
+                        // Click nel dialogo: chiudi, cura, compra o riprendi dal forziere
+                        if close_button().contains(mouse) {
+                            commands.push(Command::CloseDialog);
+                        } else {
+                            match game.npcs[npc].role {
+                                Role::Healer if heal_button().contains(mouse) => {
+                                    commands.push(Command::Heal);
+                                }
+                                Role::Merchant => {
+                                    let stock = game.npcs[npc].stock.len();
+                                    if let Some(i) =
+                                        (0..stock).find(|&i| stock_row(i).contains(mouse))
+                                    {
+                                        commands.push(Command::Buy(i));
+                                    }
+                                }
+                                Role::Stash => {
+                                    if let Some(cell) = grid_cell(mouse, stash_origin()) {
+                                        commands.push(Command::Retrieve(cell));
+                                    }
+                                }
+                                _ => {}
+                            }
+                        }
+                    } else if show_character
+                        && game.dialog.is_none()
+                        && character_panel().contains(mouse)
+                    {
+                        if let Some(i) =
+                            (0..Stat::ALL.len()).find(|&i| stat_button(i).contains(mouse))
+                        {
+                            commands.push(Command::AddStat(Stat::ALL[i]));
+                        }
+                    } else if inventory_open && inventory_panel().contains(mouse) {
+                        if let Some(cell) = inventory_cell(mouse)
+                            && let Some(i) = game.inventory.at(cell)
+                        {
+                            let pos = game.inventory.items[i].pos;
+                            drag = Some(Drag {
+                                cell,
+                                offset: (cell.0 - pos.0, cell.1 - pos.1),
+                            });
+                        } else if let Some(class) = slot_at(mouse) {
+                            // Click su uno slot: togli l'oggetto indossato
+                            commands.push(Command::Unequip(class));
+                        }
+                    } else if let Some(cell) = hover {
+                        if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+                            // Shift + click: attacca sul posto verso la cella, senza muoversi
+                            commands.push(Command::Attack(cell));
+                            in_place = Some(cell);
+                        } else {
+                            // Se nella cella c'è un mostro diventa il bersaglio:
+                            // il player lo insegue e lo attacca finché non muore
+                            commands.push(Command::MoveTo(cell));
+                        }
+                    }
+                }
+
+                // Attacco sul posto: finché il tasto resta premuto segue il mouse,
+                // al rilascio si ferma
+                if let Some(cell) = in_place {
+                    if !is_mouse_button_down(MouseButton::Left) {
+                        commands.push(Command::Stop);
+                        in_place = None;
+                    } else if let Some(to) = hover
+                        && to != cell
+                    {
+                        commands.push(Command::Attack(to));
+                        in_place = Some(to);
+                    }
+                }
+
+                // Click destro su un oggetto dell'inventario: indossalo
+                // (o vendilo al mercante, o mettilo nel forziere, se il dialogo è aperto).
+                // Altrove sulla mappa: lancia l'abilità selezionata verso quella cella
+                if is_mouse_button_pressed(MouseButton::Right) {
+                    let (mx, my) = mouse_position();
+                    let mouse = vec2(mx, my);
+                    let role = game.dialog.map(|i| game.npcs[i].role);
+                    if inventory_open && inventory_panel().contains(mouse) {
+                        if let Some(cell) = inventory_cell(mouse) {
+                            commands.push(match role {
+                                Some(Role::Merchant) => Command::Sell(cell),
+                                Some(Role::Stash) => Command::Store(cell),
+                                _ => Command::Equip(cell),
+                            });
+                        }
+                    } else if role.is_some() && dialog_panel().contains(mouse) {
+                        // Nessuna azione: il click destro nel dialogo non lancia abilità
+                    } else if let Some(target) = hover {
+                        commands.push(Command::Cast {
+                            skill: selected,
+                            target,
+                        });
+                    }
+                }
+
+                // Rilascio dell'oggetto trascinato: dentro la griglia lo sposta,
+                // su uno slot lo indossa, fuori dall'inventario lo lascia a terra
+                // sotto il player
+                if is_mouse_button_released(MouseButton::Left)
+                    && let Some(d) = drag.take()
+                {
+                    let mouse = Vec2::from(mouse_position());
+                    if let Some(cell) = inventory_cell(mouse) {
+                        if let Some(to) = drop_target(cell, &d) {
+                            commands.push(Command::MoveItem { from: d.cell, to });
+                        }
+                    } else if slot_at(mouse).is_some() {
+                        commands.push(Command::Equip(d.cell));
+                    } else if !inventory_panel().contains(mouse) {
+                        commands.push(Command::DropItem(d.cell));
+                    }
+                }
+
+                // Timestep fisso: get_frame_time() varia con gli FPS, quindi lo
+                // accumuliamo ed eseguiamo tanti tick quanti ne "stanno" dentro.
+                // Il limite evita di dover recuperare secondi interi dopo un blocco.
+                acc = (acc + get_frame_time()).min(0.25);
+                while acc >= TICK {
+                    acc -= TICK;
+                    // Registra i comandi con il tick in cui vengono eseguiti
+                    if let Some(r) = recording.as_mut() {
+                        r.record(game.tick, &commands);
+                    }
+                    // I comandi vanno al primo tick disponibile, poi la coda si svuota
+                    let over = game.update(&commands);
+                    commands.clear();
+                    if over {
+                        // Se update() ritorna true, passa a GameOver
+                        // e salva il replay della partita appena finita
+                        if let Some(Err(e)) = recording.as_ref().map(|r| r.save(REPLAY_FILE)) {
+                            message = format!("Cannot save {REPLAY_FILE}: {e}");
+                        }
+                        in_progress = false;
+                        state = AppState::GameOver;
+                        break;
+                    }
+                }
+
+                // F2: salva il replay fino a questo punto (da allegare a una segnalazione)
+                if is_key_pressed(KeyCode::F2) {
+                    message = match recording.as_ref().map(|r| r.save(REPLAY_FILE)) {
+                        Some(Ok(())) => format!("Replay saved to {REPLAY_FILE}"),
+                        Some(Err(e)) => format!("Cannot save {REPLAY_FILE}: {e}"),
+                        None => "No replay for a loaded game".to_string(),
+                    };
+                }
+
+                // Esc: torna al menu (la partita resta in pausa, si può salvare o riprendere)
+                if is_key_pressed(KeyCode::Escape) {
+                    message.clear();
+                    state = AppState::Menu;
+                }
+
+                // Il resto dell'accumulatore serve a interpolare il disegno
+                camera.update(&game, acc / TICK, get_frame_time());
+                draw(&game, &assets, &camera, acc / TICK, cutaway, hover);
+                draw_hotbar(&game, selected);
+                // Il dialogo con un NPC prende il posto della scheda del personaggio
+                if let Some(npc) = game.dialog {
+                    draw_dialog(&game, npc);
+                } else if show_character {
+                    draw_character(&game);
+                }
+                if inventory_open {
+                    draw_inventory(&game, drag.as_ref());
+                }
+                if drag.is_none() {
+                    map_cursor = hover.map(|cell| cursor_for(&game, cell));
+                }
+                draw_text(&message, 20., 30., 20., GRAY);
+            }
+
+            // Visualizzatore di replay: pausa, avanzamento a passi e velocità
+            AppState::Replay => {
+                let Some(v) = view.as_mut() else {
+                    state = AppState::Menu;
+                    next_frame().await;
+                    continue;
+                };
+
+                if is_key_pressed(KeyCode::Space) {
+                    v.paused = !v.paused;
+                }
+                camera.zoom_by(mouse_wheel().1, &game);
+                if is_key_pressed(KeyCode::Up) {
+                    v.speed = (v.speed * 2.).min(8.);
+                }
+                if is_key_pressed(KeyCode::Down) {
+                    v.speed = (v.speed / 2.).max(0.25);
+                }
+
+                // Il replay è finito quando arriva al punto in cui si era fermata
+                // la registrazione, oppure al game over
+                let mut done = v.playback.finished(&game) || game.hp <= 0;
+                if v.paused {
+                    // In pausa: freccia destra = avanza di un solo tick
+                    if is_key_pressed(KeyCode::Right) && !done {
+                        v.playback.step(&mut game);
+                    }
+                    acc = 0.;
+                } else {
+                    // Stesso timestep fisso del gioco, con il tempo moltiplicato per la velocità
+                    acc = (acc + get_frame_time() * v.speed).min(0.25 * v.speed.max(1.));
+                    while acc >= TICK && !done {
+                        acc -= TICK;
+                        done = v.playback.step(&mut game) || v.playback.finished(&game);
+                    }
+                }
+
+                let alpha = if done { 0. } else { acc / TICK };
+                camera.update(&game, alpha, get_frame_time());
+                draw(&game, &assets, &camera, alpha, cutaway, None);
+                let status = if done {
+                    "END"
+                } else if v.paused {
+                    "PAUSED"
+                } else {
+                    ""
+                };
+                draw_text(
+                    &format!(
+                        "REPLAY  tick {}/{}  x{}  {status}",
+                        game.tick,
+                        v.playback.end(),
+                        v.speed
+                    ),
+                    20.,
+                    30.,
+                    24.,
+                    SKYBLUE,
+                );
+                draw_text(
+                    "Space: pause  Right: step  Up/Down: speed  Esc: menu",
+                    20.,
+                    55.,
+                    18.,
+                    GRAY,
+                );
+
+                if is_key_pressed(KeyCode::Escape) {
+                    view = None;
+                    state = AppState::Menu;
                 }
-                game.draw();
             }
 
             // Schermata game over
             AppState::GameOver => {
                 // Disegna il gioco "congelato" sotto l'overlay
-                game.draw();
+                draw(&game, &assets, &camera, 0., cutaway, None);
                 // Overlay bianco semi-trasparente
                 draw_rectangle(
                     0.,
@@ -528,6 +2127,16 @@ async fn main() {
                 }
             }
         }
+        // Sulla mappa il puntatore del sistema lascia il posto a quello del gioco
+        if map_cursor.is_some() == system_cursor {
+            system_cursor = !system_cursor;
+            show_mouse(system_cursor);
+        }
+        if let Some(cursor) = map_cursor {
+            let (mx, my) = mouse_position();
+            draw_cursor(cursor, mx, my);
+        }
+
         // Aspetta il prossimo frame (necessario per macroquad async)
         next_frame().await;
     }
@@ -0,0 +1,134 @@
+// Mappa di gioco con dimensioni decise a runtime
+//
+// Prima la mappa era un array fisso [[Tile; MAP]; MAP]: la dimensione doveva
+// essere nota a compile time. Grid<T> invece salva le celle in un Vec (heap),
+// quindi possiamo creare livelli 20x20, 200x200 o rettangolari.
+//
+// Le celle sono salvate "riga per riga" in un unico Vec:
+// la cella (x, y) si trova all'indice y * larghezza + x.
+
+use serde::{Deserialize, Serialize};
+use std::ops::{Index, IndexMut};
+
+// Enum per i tipi di celle della mappa
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Tile {
+    Wall,       // Muro: blocca il movimento
+    Floor,      // Pavimento: calpestabile
+    Rubble,     // Macerie: calpestabili ma rallentano
+    Water,      // Acqua bassa: calpestabile ma molto lenta
+    StairsDown, // Scale verso il piano inferiore
+    StairsUp,   // Scale verso il piano superiore
+}
+
+impl Tile {
+    // Costo di movimento per entrare nella cella (moltiplicatore del passo).
+    // None = cella non attraversabile.
+    pub fn cost(self) -> Option<u32> {
+        match self {
+            Tile::Wall => None,
+            Tile::Floor | Tile::StairsDown | Tile::StairsUp => Some(1),
+            Tile::Rubble => Some(2),
+            Tile::Water => Some(3),
+        }
+    }
+
+    // true se si può camminare sulla cella (qualsiasi terreno tranne i muri)
+    pub fn walkable(self) -> bool {
+        self.cost().is_some()
+    }
+}
+
+// Griglia generica 2D: usata per la mappa (Grid<Tile>) ma anche per le
+// matrici di supporto degli algoritmi (visited, parent, ...)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Grid<T> {
+    w: usize,
+    h: usize,
+    cells: Vec<T>,
+}
+
+// La mappa di gioco è una griglia di Tile
+pub type Map = Grid<Tile>;
+
+impl<T: Clone> Grid<T> {
+    // Crea una griglia w x h con tutte le celle uguali a fill
+    pub fn new(w: usize, h: usize, fill: T) -> Self {
+        Grid {
+            w,
+            h,
+            cells: vec![fill; w * h],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn width(&self) -> usize {
+        self.w
+    }
+
+    pub fn height(&self) -> usize {
+        self.h
+    }
+
+    // true se il numero di celle corrisponde alle dimensioni
+    // (può essere falso solo per una griglia letta da un file danneggiato)
+    pub fn is_consistent(&self) -> bool {
+        self.cells.len() == self.w * self.h
+    }
+
+    // true se (x, y) è dentro la griglia
+    pub fn in_bounds(&self, x: usize, y: usize) -> bool {
+        x < self.w && y < self.h
+    }
+
+    // Come l'indicizzazione, ma ritorna None fuori dai bordi invece di andare in panic
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        if self.in_bounds(x, y) {
+            Some(&self.cells[y * self.w + x])
+        } else {
+            None
+        }
+    }
+
+    // Itera su tutte le celle con le loro coordinate: ((x, y), &cella)
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        self.cells
+            .iter()
+            .enumerate()
+            .map(|(i, c)| ((i % self.w, i / self.w), c))
+    }
+
+    // Come iter(), ma con accesso mutabile alle celle
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = ((usize, usize), &mut T)> {
+        let w = self.w;
+        self.cells
+            .iter_mut()
+            .enumerate()
+            .map(move |(i, c)| ((i % w, i / w), c))
+    }
+}
+
+impl Map {
+    // true se la cella è dentro la mappa ed è calpestabile
+    pub fn is_walkable(&self, x: usize, y: usize) -> bool {
+        self.get(x, y).is_some_and(|t| t.walkable())
+    }
+}
+
+// Indicizzazione con map[(x, y)]: nota l'ordine (x, y), non [y][x] come negli array
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        assert!(self.in_bounds(x, y), "cella ({x}, {y}) fuori dalla mappa");
+        &self.cells[y * self.w + x]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        assert!(self.in_bounds(x, y), "cella ({x}, {y}) fuori dalla mappa");
+        &mut self.cells[y * self.w + x]
+    }
+}
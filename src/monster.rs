@@ -0,0 +1,103 @@
+// Tipi di mostro definiti da dati
+//
+// Le statistiche non sono scritte nel codice: stanno in data/monsters.ron,
+// così per bilanciare il gioco o aggiungere un mostro basta modificare il file.
+// Il file viene incluso nel binario in fase di compilazione (include_str!):
+// la libreria non legge nulla dal disco e i replay restano riproducibili.
+
+use crate::damage::{DamageType, Resists};
+use crate::rng::Rng;
+use crate::status::Inflict;
+use serde::{Deserialize, Serialize};
+
+// Contenuto di data/monsters.ron
+const MONSTERS_RON: &str = include_str!("../data/monsters.ron");
+
+// Forma della testa dello stickman
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Sprite {
+    Horns, // Due corna a V
+    Skull, // Teschio con occhi vuoti
+    Hood,  // Cappuccio a punta (incantatori)
+}
+
+// Come si comporta il mostro quando può agire
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Behaviour {
+    Chase,                // Insegue sempre il player
+    Guard { aggro: u32 }, // Resta fermo finché il player non è entro `aggro` passi
+    Kite { keep: u32 },   // Attacca da lontano e si allontana se il player è a meno di `keep` celle
+}
+
+// Definizione di un tipo di mostro
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MonsterKind {
+    pub name: String,
+    pub max_hp: i32,       // Punti vita iniziali
+    pub damage: i32,       // Danno di ogni attacco
+    pub attack_range: u32, // Distanza di attacco in celle (1 = corpo a corpo)
+    pub move_speed: f32,   // Celle al secondo su terreno normale
+    pub attack_cd: f32,    // Secondi tra due attacchi
+    #[serde(default)]
+    pub projectile_speed: f32, // Celle al secondo dei proiettili (solo se attack_range > 1)
+    pub color: (f32, f32, f32), // Colore RGB (0..1) usato dal front end
+    pub sprite: Sprite,    // Forma della testa
+    pub scale: f32,        // Dimensione rispetto allo stickman normale
+    pub behaviour: Behaviour, // Intelligenza artificiale
+    pub weight: u32,       // Frequenza di spawn (relativa agli altri tipi)
+    pub xp: u32,           // Esperienza data al player quando muore
+    #[serde(default)]
+    pub loot: String, // Tabella di loot in data/items.ron (vuota = nessun drop)
+    #[serde(default)]
+    pub inflict: Option<Inflict>, // Effetto di stato dei suoi colpi
+    #[serde(default)]
+    pub damage_type: DamageType, // Tipo di danno dei suoi colpi (fisico se non indicato)
+    #[serde(default)]
+    pub resist: Resists, // Resistenze in percentuale
+    #[serde(default)]
+    pub dodge: f32, // Probabilità (0..1) di schivare i colpi del player
+    #[serde(default)]
+    pub block: f32, // Probabilità (0..1) di parare i colpi del player
+}
+
+// Legge una lista di tipi di mostro in formato RON
+pub fn parse_kinds(text: &str) -> Result<Vec<MonsterKind>, String> {
+    let kinds: Vec<MonsterKind> = ron::from_str(text).map_err(|e| e.to_string())?;
+    if kinds.is_empty() {
+        return Err("no monster kinds defined".into());
+    }
+    if let Some(k) = kinds.iter().find(|k| k.move_speed <= 0. || k.max_hp <= 0) {
+        return Err(format!(
+            "{}: max_hp and move_speed must be positive",
+            k.name
+        ));
+    }
+    if let Some(k) = kinds
+        .iter()
+        .find(|k| k.attack_range > 1 && k.projectile_speed <= 0.)
+    {
+        return Err(format!("{}: ranged kinds need a projectile_speed", k.name));
+    }
+    if kinds.iter().all(|k| k.weight == 0) {
+        return Err("at least one monster kind needs a weight above 0".into());
+    }
+    Ok(kinds)
+}
+
+// Sceglie a caso l'indice di un tipo, in proporzione al suo weight
+pub fn pick_kind(kinds: &[MonsterKind], rng: &mut Rng) -> usize {
+    let total: u32 = kinds.iter().map(|k| k.weight).sum();
+    let mut roll = rng.range(0, total as usize) as u32;
+    for (i, k) in kinds.iter().enumerate() {
+        if roll < k.weight {
+            return i;
+        }
+        roll -= k.weight;
+    }
+    0
+}
+
+// Tipi di mostro standard (data/monsters.ron)
+pub fn default_kinds() -> Vec<MonsterKind> {
+    parse_kinds(MONSTERS_RON).unwrap_or_else(|e| panic!("data/monsters.ron: {e}"))
+}
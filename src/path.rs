@@ -0,0 +1,478 @@
+// Pathfinding: BFS, A* (A-star) con terreni pesati e movimento in diagonale,
+// flow field di Dijkstra per i mostri
+//
+// BFS trova il percorso con meno PASSI, ma tratta tutte le celle allo stesso
+// modo. A* invece trova il percorso più ECONOMICO: ogni cella ha un costo
+// (Tile::cost) e un passo in diagonale costa più di uno dritto.
+//
+// A* esplora per prime le celle con f = g + h più basso:
+// - g: costo reale per arrivare dallo start alla cella
+// - h: stima (euristica) del costo per andare dalla cella al goal
+// Finché l'euristica non sovrastima mai il costo reale, il percorso è ottimo.
+
+use crate::map::{Grid, Map};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+// Calcola la distanza Manhattan tra due punti sulla griglia
+// La distanza Manhattan è la somma delle differenze assolute delle coordinate:
+// |x1-x2| + |y1-y2|. Si chiama così perché rappresenta la distanza percorsa
+// in una griglia (come le strade di Manhattan), dove puoi muoverti solo
+// in orizzontale o verticale, mai in diagonale.
+// Usata dal generatore per tenere i mostri lontani dal player allo spawn
+pub fn dist(p1: (usize, usize), p2: (usize, usize)) -> i32 {
+    (p1.0 as i32 - p2.0 as i32).abs() + (p1.1 as i32 - p2.1 as i32).abs()
+}
+
+// Pathfinding: Breadth-First Search (BFS)
+// Trova il percorso più breve tra start e goal evitando i muri.
+// Ritorna un Vec con le coordinate del percorso (escluso start, incluso goal).
+// Ritorna vec vuoto se non esiste un percorso.
+//
+// BFS esplora "a onde concentriche": prima tutte le celle a distanza 1,
+// poi quelle a distanza 2, ecc. La coda FIFO (First In, First Out) garantisce
+// questo ordine. Se usassimo uno stack LIFO avremmo DFS (Depth-First Search),
+// che va "in profondità" e non garantisce il percorso più breve.
+pub fn bfs(map: &Map, start: (usize, usize), goal: (usize, usize)) -> Vec<(usize, usize)> {
+    // Coda FIFO: celle da esplorare. BFS usa FIFO per garantire il percorso più breve.
+    let mut q = VecDeque::from([start]);
+
+    // Matrice visited: traccia le celle già visitate per evitare loop infiniti.
+    // Senza questo, l'algoritmo continuerebbe a visitare le stesse celle (dead loop).
+    // Le matrici hanno le stesse dimensioni (runtime) della mappa.
+    let mut visited = Grid::new(map.width(), map.height(), false);
+    visited[start] = true;
+
+    // Matrice parent: per ogni cella, memorizza da quale cella ci siamo arrivati.
+    // Serve per ricostruire il percorso una volta raggiunto il goal.
+    let mut parent: Grid<Option<(usize, usize)>> = Grid::new(map.width(), map.height(), None);
+
+    // Estrai celle dalla coda finché non è vuota
+    while let Some(curr) = q.pop_front() {
+        // Se abbiamo raggiunto il goal, ricostruiamo il percorso
+        if curr == goal {
+            let mut path = vec![];
+            let mut c = goal;
+            // Risaliamo i parent dal goal fino allo start
+            while c != start {
+                path.push(c);
+                c = parent[c].unwrap();
+            }
+            // Il percorso è al contrario (goal→start), lo invertiamo
+            path.reverse();
+            return path;
+        }
+
+        // Esplora i 4 vicini (su, giù, sinistra, destra)
+        for (dx, dy) in [(0, -1), (0, 1), (-1, 0), (1, 0)] {
+            let (nx, ny) = ((curr.0 as i32 + dx) as usize, (curr.1 as i32 + dy) as usize);
+
+            // Controlla: dentro i bounds, non è un muro, non già visitata
+            if map.is_walkable(nx, ny) && !visited[(nx, ny)] {
+                visited[(nx, ny)] = true; // Marca come visitata PRIMA di aggiungere alla coda
+                parent[(nx, ny)] = Some(curr); // Ricorda da dove siamo arrivati
+                q.push_back((nx, ny)); // Aggiungi alla coda per esplorarla dopo
+            }
+        }
+    }
+    // Coda vuota e goal non raggiunto = nessun percorso possibile
+    vec![]
+}
+
+// Costo di un passo dritto e di uno in diagonale (≈ 10 * √2).
+// Usiamo interi per evitare i problemi di confronto tra f32.
+pub const STEP: u32 = 10;
+pub const DIAG_STEP: u32 = 14;
+
+// Euristica: stima del costo tra due celle. È una funzione "pluggable":
+// si può passare octile, manhattan, dijkstra o una propria funzione.
+pub type Heuristic = fn((usize, usize), (usize, usize)) -> u32;
+
+// Direzioni di movimento consentite
+#[derive(Copy, Clone, PartialEq)]
+pub enum Moves {
+    Four,  // Solo su, giù, sinistra, destra
+    Eight, // Anche in diagonale
+}
+
+const DIRS_4: [(i32, i32); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+const DIRS_8: [(i32, i32); 8] = [
+    (0, -1),
+    (0, 1),
+    (-1, 0),
+    (1, 0),
+    (-1, -1),
+    (1, -1),
+    (-1, 1),
+    (1, 1),
+];
+
+impl Moves {
+    fn dirs(self) -> &'static [(i32, i32)] {
+        match self {
+            Moves::Four => &DIRS_4,
+            Moves::Eight => &DIRS_8,
+        }
+    }
+}
+
+// Distanza "octile": la distanza esatta su una griglia a 8 direzioni senza
+// ostacoli (prima le diagonali, poi il resto in linea retta)
+pub fn octile(a: (usize, usize), b: (usize, usize)) -> u32 {
+    let dx = a.0.abs_diff(b.0) as u32;
+    let dy = a.1.abs_diff(b.1) as u32;
+    STEP * dx.max(dy) + (DIAG_STEP - STEP) * dx.min(dy)
+}
+
+// Distanza Manhattan in unità di costo: adatta al movimento a 4 direzioni
+pub fn manhattan(a: (usize, usize), b: (usize, usize)) -> u32 {
+    STEP * (a.0.abs_diff(b.0) + a.1.abs_diff(b.1)) as u32
+}
+
+// Euristica nulla: A* diventa l'algoritmo di Dijkstra (esplora in tutte le direzioni)
+pub fn dijkstra(_: (usize, usize), _: (usize, usize)) -> u32 {
+    0
+}
+
+// Costo del passo da `from` a `to` (celle adiacenti), oppure None se il passo
+// non è consentito. In diagonale non si possono "tagliare gli angoli":
+// entrambe le celle ortogonali attraversate devono essere calpestabili.
+pub fn step_cost(map: &Map, from: (usize, usize), to: (usize, usize)) -> Option<u32> {
+    let dx = from.0.abs_diff(to.0);
+    let dy = from.1.abs_diff(to.1);
+    if dx > 1 || dy > 1 || dx + dy == 0 {
+        return None;
+    }
+    let tile = *map.get(to.0, to.1)?;
+    let cost = tile.cost()?;
+
+    if dx == 1 && dy == 1 {
+        if !map.is_walkable(to.0, from.1) || !map.is_walkable(from.0, to.1) {
+            return None;
+        }
+        Some(DIAG_STEP * cost)
+    } else {
+        Some(STEP * cost)
+    }
+}
+
+// Cerca il percorso più economico da start a goal.
+// Come bfs: ritorna le celle del percorso (escluso start, incluso goal),
+// oppure un vec vuoto se il goal non è raggiungibile.
+pub fn astar(
+    map: &Map,
+    start: (usize, usize),
+    goal: (usize, usize),
+    moves: Moves,
+    heuristic: Heuristic,
+) -> Vec<(usize, usize)> {
+    // g: miglior costo trovato finora per ogni cella
+    let mut g = Grid::new(map.width(), map.height(), u32::MAX);
+    let mut parent: Grid<Option<(usize, usize)>> = Grid::new(map.width(), map.height(), None);
+    g[start] = 0;
+
+    // Coda con priorità: BinaryHeap è un max-heap, Reverse lo rende un min-heap.
+    // A parità di f preferiamo la h più bassa (celle più vicine al goal).
+    let mut open = BinaryHeap::new();
+    let h0 = heuristic(start, goal);
+    open.push(Reverse((h0, h0, start)));
+
+    while let Some(Reverse((f, h, curr))) = open.pop() {
+        if curr == goal {
+            let mut path = vec![];
+            let mut c = goal;
+            while c != start {
+                path.push(c);
+                c = parent[c].unwrap();
+            }
+            path.reverse();
+            return path;
+        }
+
+        // Voce vecchia nella coda: abbiamo già trovato un modo più economico
+        if f - h > g[curr] {
+            continue;
+        }
+
+        for &(dx, dy) in moves.dirs() {
+            let next = ((curr.0 as i32 + dx) as usize, (curr.1 as i32 + dy) as usize);
+            let Some(cost) = step_cost(map, curr, next) else {
+                continue;
+            };
+
+            let new_g = g[curr] + cost;
+            if new_g < g[next] {
+                g[next] = new_g;
+                parent[next] = Some(curr);
+                let h = heuristic(next, goal);
+                open.push(Reverse((new_g + h, h, next)));
+            }
+        }
+    }
+    vec![]
+}
+
+// Flow field (mappa delle distanze di Dijkstra) calcolata a partire da un'origine
+//
+// Invece di far calcolare a ogni mostro il proprio percorso verso il player,
+// calcoliamo UNA volta (quando il player si muove) il costo per raggiungere il
+// player da ogni cella della mappa. Ogni mostro poi deve solo guardare le celle
+// vicine e scendere verso quella con la distanza più bassa, come l'acqua
+// che scorre in discesa.
+pub struct FlowField {
+    origin: (usize, usize),
+    dist: Grid<u32>,
+}
+
+// FlowField vuota: segnaposto per una partita appena caricata da file,
+// prima che venga ricalcolata
+impl Default for FlowField {
+    fn default() -> Self {
+        FlowField {
+            origin: (0, 0),
+            dist: Grid::new(0, 0, u32::MAX),
+        }
+    }
+}
+
+impl FlowField {
+    // Dijkstra dall'origine verso tutte le celle raggiungibili
+    pub fn new(map: &Map, origin: (usize, usize)) -> Self {
+        let mut dist = Grid::new(map.width(), map.height(), u32::MAX);
+        dist[origin] = 0;
+        let mut open = BinaryHeap::from([Reverse((0, origin))]);
+
+        while let Some(Reverse((d, curr))) = open.pop() {
+            if d > dist[curr] {
+                continue;
+            }
+            for &(dx, dy) in Moves::Eight.dirs() {
+                let next = ((curr.0 as i32 + dx) as usize, (curr.1 as i32 + dy) as usize);
+                // Il mostro percorrerà il passo al contrario (da next verso curr),
+                // quindi usiamo il costo di quel passo. step_cost controlla solo
+                // la cella d'arrivo: i muri non hanno distanza
+                if !map.is_walkable(next.0, next.1) {
+                    continue;
+                }
+                let Some(cost) = step_cost(map, next, curr) else {
+                    continue;
+                };
+                if d + cost < dist[next] {
+                    dist[next] = d + cost;
+                    open.push(Reverse((d + cost, next)));
+                }
+            }
+        }
+        FlowField { origin, dist }
+    }
+
+    // Cella da cui è stata calcolata la field (la posizione del player)
+    pub fn origin(&self) -> (usize, usize) {
+        self.origin
+    }
+
+    // Costo per raggiungere l'origine da pos (None se irraggiungibile)
+    pub fn distance(&self, pos: (usize, usize)) -> Option<u32> {
+        self.dist
+            .get(pos.0, pos.1)
+            .copied()
+            .filter(|&d| d != u32::MAX)
+    }
+
+    // Prossimo passo da `from` verso l'origine, evitando le celle bloccate
+    // (es. occupate da altri mostri).
+    // Considera solo i vicini che avvicinano davvero all'origine: se il vicino
+    // migliore è occupato prova il successivo, così i mostri aggirano chi
+    // sta davanti invece di restare fermi. None se nessun passo è possibile.
+    pub fn next_step(
+        &self,
+        map: &Map,
+        from: (usize, usize),
+        blocked: impl Fn((usize, usize)) -> bool,
+    ) -> Option<(usize, usize)> {
+        let here = self.distance(from)?;
+        let mut best: Option<(u32, (usize, usize))> = None;
+
+        for &(dx, dy) in Moves::Eight.dirs() {
+            let next = ((from.0 as i32 + dx) as usize, (from.1 as i32 + dy) as usize);
+            let (Some(cost), Some(d)) = (step_cost(map, from, next), self.distance(next)) else {
+                continue;
+            };
+            if d >= here || blocked(next) {
+                continue;
+            }
+            // Costo totale passando da next: passo + distanza rimanente
+            if best.is_none_or(|(b, _)| cost + d < b) {
+                best = Some((cost + d, next));
+            }
+        }
+        best.map(|(_, next)| next)
+    }
+
+    // Il contrario di next_step: un passo che ALLONTANA dall'origine
+    // (per i mostri a distanza che vogliono tenersi lontani dal player).
+    // Sceglie il vicino libero con la distanza più alta. None se è in un vicolo cieco.
+    pub fn step_away(
+        &self,
+        map: &Map,
+        from: (usize, usize),
+        blocked: impl Fn((usize, usize)) -> bool,
+    ) -> Option<(usize, usize)> {
+        let here = self.distance(from)?;
+        let mut best: Option<(u32, (usize, usize))> = None;
+
+        for &(dx, dy) in Moves::Eight.dirs() {
+            let next = ((from.0 as i32 + dx) as usize, (from.1 as i32 + dy) as usize);
+            let (Some(_), Some(d)) = (step_cost(map, from, next), self.distance(next)) else {
+                continue;
+            };
+            if d <= here || blocked(next) {
+                continue;
+            }
+            if best.is_none_or(|(b, _)| d > b) {
+                best = Some((d, next));
+            }
+        }
+        best.map(|(_, next)| next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::{self, GenMode};
+    use crate::map::Tile;
+    use crate::rng::Rng;
+
+    // Mappa w x h di pavimento circondata da muri
+    fn open_map(w: usize, h: usize) -> Map {
+        let mut map = Map::new(w, h, Tile::Wall);
+        for y in 1..h - 1 {
+            for x in 1..w - 1 {
+                map[(x, y)] = Tile::Floor;
+            }
+        }
+        map
+    }
+
+    // Costo totale di un percorso (None se contiene un passo non consentito)
+    fn path_cost(map: &Map, start: (usize, usize), path: &[(usize, usize)]) -> Option<u32> {
+        let mut cost = 0;
+        let mut prev = start;
+        for &c in path {
+            cost += step_cost(map, prev, c)?;
+            prev = c;
+        }
+        Some(cost)
+    }
+
+    #[test]
+    fn diagonal_steps_do_not_cut_corners() {
+        let mut map = open_map(5, 5);
+        assert_eq!(step_cost(&map, (1, 1), (2, 2)), Some(DIAG_STEP));
+        map[(2, 1)] = Tile::Wall;
+        assert_eq!(step_cost(&map, (1, 1), (2, 2)), None);
+        assert_eq!(step_cost(&map, (2, 2), (1, 1)), None);
+        assert_eq!(step_cost(&map, (1, 1), (1, 2)), Some(STEP));
+
+        // Con un muro di traverso A* gira attorno all'angolo invece di tagliarlo
+        let path = astar(&map, (1, 1), (3, 1), Moves::Eight, octile);
+        assert_eq!(path.last(), Some(&(3, 1)));
+        assert!(path_cost(&map, (1, 1), &path).is_some());
+    }
+
+    #[test]
+    fn tile_costs_change_the_route() {
+        let mut map = open_map(7, 5);
+        let path = astar(&map, (1, 2), (5, 2), Moves::Eight, octile);
+        assert!(path.contains(&(3, 2)));
+
+        // L'acqua costa il triplo: conviene passarle di fianco in diagonale
+        map[(3, 2)] = Tile::Water;
+        let path = astar(&map, (1, 2), (5, 2), Moves::Eight, octile);
+        assert_eq!(path.last(), Some(&(5, 2)));
+        assert!(!path.contains(&(3, 2)));
+        assert_eq!(
+            path_cost(&map, (1, 2), &path),
+            Some(2 * STEP + 2 * DIAG_STEP)
+        );
+    }
+
+    #[test]
+    fn octile_paths_are_as_cheap_as_dijkstra() {
+        for mode in [GenMode::Rooms, GenMode::Caves] {
+            let map = dungeon::generate(&mut Rng::new(7), mode, 30, 20, 0, true).map;
+            let cells: Vec<_> = map
+                .iter()
+                .filter(|(_, t)| t.walkable())
+                .map(|(c, _)| c)
+                .collect();
+            for i in (0..cells.len()).step_by(17) {
+                let (start, goal) = (cells[i], cells[(i * 7 + 3) % cells.len()]);
+                let fast = astar(&map, start, goal, Moves::Eight, octile);
+                let slow = astar(&map, start, goal, Moves::Eight, dijkstra);
+                assert_eq!(
+                    path_cost(&map, start, &fast),
+                    path_cost(&map, start, &slow),
+                    "{start:?} -> {goal:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unreachable_goals_give_an_empty_path() {
+        let mut map = open_map(7, 7);
+        for (x, y) in [(4, 4), (4, 5), (5, 4)] {
+            map[(x, y)] = Tile::Wall;
+        }
+        // (5, 5) è chiusa tra tre muri e il bordo della mappa
+        for moves in [Moves::Four, Moves::Eight] {
+            assert!(astar(&map, (1, 1), (5, 5), moves, octile).is_empty());
+        }
+        assert!(astar(&map, (1, 1), (4, 4), Moves::Eight, octile).is_empty());
+        assert!(bfs(&map, (1, 1), (5, 5)).is_empty());
+    }
+
+    #[test]
+    fn flow_field_distances_match_the_cheapest_paths() {
+        let map = dungeon::generate(&mut Rng::new(3), GenMode::Caves, 30, 20, 0, true).map;
+        let cells: Vec<_> = map
+            .iter()
+            .filter(|(_, t)| t.walkable())
+            .map(|(c, _)| c)
+            .collect();
+        let origin = cells[cells.len() / 2];
+        let field = FlowField::new(&map, origin);
+        assert_eq!(field.distance(origin), Some(0));
+        for (cell, tile) in map.iter() {
+            if !tile.walkable() {
+                assert_eq!(field.distance(cell), None);
+                continue;
+            }
+            // Raggiungibile come con BFS, e con il costo del percorso più economico
+            let reachable = cell == origin || !bfs(&map, cell, origin).is_empty();
+            let cheapest = astar(&map, cell, origin, Moves::Eight, dijkstra);
+            let cost = reachable.then(|| path_cost(&map, cell, &cheapest).unwrap());
+            assert_eq!(field.distance(cell), cost, "{cell:?}");
+        }
+    }
+
+    #[test]
+    fn next_step_goes_around_occupied_cells() {
+        let map = open_map(9, 5);
+        let field = FlowField::new(&map, (7, 2));
+        assert_eq!(field.next_step(&map, (1, 2), |_| false), Some((2, 2)));
+
+        // Con la cella davanti occupata prende una diagonale che avvicina comunque
+        let next = field.next_step(&map, (1, 2), |c| c == (2, 2)).unwrap();
+        assert_ne!(next, (2, 2));
+        assert!(field.distance(next) < field.distance((1, 2)));
+
+        // In un corridoio largo una cella non c'è modo di aggirarlo
+        let mut corridor = open_map(9, 3);
+        corridor[(4, 1)] = Tile::Floor;
+        let field = FlowField::new(&corridor, (7, 1));
+        assert_eq!(field.next_step(&corridor, (1, 1), |c| c == (2, 1)), None);
+    }
+}
@@ -0,0 +1,189 @@
+// Scheda del personaggio: statistiche, esperienza e livelli
+//
+// Le quattro statistiche base (come in Diablo) determinano i valori derivati:
+// - forza      → danno
+// - destrezza  → velocità di attacco/movimento e difesa
+// - vitalità   → punti vita massimi
+// - energia    → mana massimo
+// Uccidendo i mostri si guadagna esperienza; a ogni livello si ottengono
+// punti da spendere nelle statistiche.
+// Anche l'equipaggiamento indossato modifica statistiche e valori derivati.
+
+use crate::damage::Resists;
+use crate::item::Bonuses;
+use serde::{Deserialize, Serialize};
+
+// Punti statistica guadagnati a ogni livello
+pub const POINTS_PER_LEVEL: u32 = 5;
+
+// Le statistiche su cui si possono spendere i punti
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Stat {
+    Strength,
+    Dexterity,
+    Vitality,
+    Energy,
+}
+
+impl Stat {
+    pub const ALL: [Stat; 4] = [
+        Stat::Strength,
+        Stat::Dexterity,
+        Stat::Vitality,
+        Stat::Energy,
+    ];
+
+    // Nome mostrato nella scheda (e usato nei replay)
+    pub fn name(self) -> &'static str {
+        match self {
+            Stat::Strength => "strength",
+            Stat::Dexterity => "dexterity",
+            Stat::Vitality => "vitality",
+            Stat::Energy => "energy",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Character {
+    pub strength: i32,
+    pub dexterity: i32,
+    pub vitality: i32,
+    pub energy: i32,
+    pub level: u32,
+    pub xp: u32,     // Esperienza totale accumulata
+    pub points: u32, // Punti statistica ancora da spendere
+    // Bonus dell'equipaggiamento indossato (aggiornati quando cambia)
+    pub gear: Bonuses,
+}
+
+impl Character {
+    // Personaggio di livello 1 con le statistiche iniziali
+    pub fn new() -> Self {
+        Character {
+            strength: 15,
+            dexterity: 10,
+            vitality: 15,
+            energy: 10,
+            level: 1,
+            xp: 0,
+            points: 0,
+            gear: Bonuses::default(),
+        }
+    }
+
+    // Valore attuale di una statistica (punti spesi + bonus dell'equipaggiamento)
+    pub fn stat(&self, stat: Stat) -> i32 {
+        match stat {
+            Stat::Strength => self.strength + self.gear.strength,
+            Stat::Dexterity => self.dexterity + self.gear.dexterity,
+            Stat::Vitality => self.vitality + self.gear.vitality,
+            Stat::Energy => self.energy + self.gear.energy,
+        }
+    }
+
+    // Spende un punto su una statistica. Ritorna false se non ci sono punti
+    pub fn spend(&mut self, stat: Stat) -> bool {
+        if self.points == 0 {
+            return false;
+        }
+        self.points -= 1;
+        match stat {
+            Stat::Strength => self.strength += 1,
+            Stat::Dexterity => self.dexterity += 1,
+            Stat::Vitality => self.vitality += 1,
+            Stat::Energy => self.energy += 1,
+        }
+        true
+    }
+
+    // Punti vita massimi: 4 per punto di vitalità + 2 per livello
+    pub fn max_hp(&self) -> i32 {
+        40 + self.stat(Stat::Vitality) * 4 + (self.level as i32 - 1) * 2 + self.gear.max_hp
+    }
+
+    // Mana massimo: 2 per punto di energia + 1 per livello
+    pub fn max_mana(&self) -> i32 {
+        self.stat(Stat::Energy) * 2 + (self.level as i32 - 1) + self.gear.max_mana
+    }
+
+    // Danno minimo e massimo di un colpo: quello dell'arma (5-9 a mani nude),
+    // +1 ogni 5 punti di forza, + il danno aggiunto dagli oggetti
+    pub fn damage(&self) -> (i32, i32) {
+        let (lo, hi) = self.gear.weapon.unwrap_or((5, 9));
+        let bonus = self.stat(Stat::Strength) / 5 + self.gear.damage;
+        (lo + bonus, hi + bonus)
+    }
+
+    // Secondi per un'azione (passo o colpo) su terreno normale.
+    // Con 10 di destrezza vale 0.15 s; più destrezza = più veloce, e la
+    // velocità di attacco degli oggetti la riduce ancora in percentuale
+    pub fn action_time(&self) -> f32 {
+        let speed = 1. + self.gear.attack_speed as f32 / 100.;
+        0.15 * 100. / (90. + self.stat(Stat::Dexterity) as f32) / speed
+    }
+
+    // Mana recuperato ogni secondo: 1 + 1 ogni 10 punti di energia
+    pub fn mana_regen(&self) -> f32 {
+        1. + self.stat(Stat::Energy) as f32 / 10.
+    }
+
+    // Difesa: riduce il danno dei colpi subiti
+    pub fn defence(&self) -> i32 {
+        self.stat(Stat::Dexterity) / 2 + self.stat(Stat::Vitality) / 5 + self.gear.defence
+    }
+
+    // Danno effettivamente subito dopo la difesa (sempre almeno 1)
+    pub fn reduce(&self, damage: i32) -> i32 {
+        (damage * 50 / (50 + self.defence())).max(1)
+    }
+
+    // Resistenze ai tipi di danno (solo dagli oggetti: il fisico lo riduce la difesa)
+    pub fn resists(&self) -> Resists {
+        Resists {
+            physical: 0,
+            fire: self.gear.fire_resist,
+            cold: self.gear.cold_resist,
+            lightning: self.gear.lightning_resist,
+            poison: self.gear.poison_resist,
+        }
+    }
+
+    // Probabilità di colpo critico: 5% + 1% ogni 5 punti di destrezza + oggetti
+    pub fn crit_chance(&self) -> f32 {
+        (5 + self.stat(Stat::Dexterity) / 5 + self.gear.crit) as f32 / 100.
+    }
+
+    // Probabilità di schivare un colpo: 1% ogni 2 punti di destrezza (massimo 40%)
+    pub fn dodge_chance(&self) -> f32 {
+        (self.stat(Stat::Dexterity) / 2).min(40) as f32 / 100.
+    }
+
+    // Probabilità di parare un colpo: solo dagli oggetti (massimo 75%)
+    pub fn block_chance(&self) -> f32 {
+        self.gear.block.min(75) as f32 / 100.
+    }
+
+    // Esperienza totale necessaria per raggiungere il livello successivo
+    pub fn next_level_xp(&self) -> u32 {
+        50 * self.level * (self.level + 1)
+    }
+
+    // Aggiunge esperienza. Ritorna il numero di livelli guadagnati
+    pub fn gain_xp(&mut self, amount: u32) -> u32 {
+        self.xp += amount;
+        let mut levels = 0;
+        while self.xp >= self.next_level_xp() {
+            self.level += 1;
+            self.points += POINTS_PER_LEVEL;
+            levels += 1;
+        }
+        levels
+    }
+}
+
+impl Default for Character {
+    fn default() -> Self {
+        Character::new()
+    }
+}
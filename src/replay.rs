@@ -0,0 +1,321 @@
+// Registrazione e riproduzione delle partite (replay)
+//
+// Visto che la simulazione è deterministica, per rivedere una partita basta
+// salvare seed, modalità e dimensione della mappa più la lista dei comandi del
+// giocatore con il tick in cui sono arrivati. Rieseguendo gli stessi comandi
+// agli stessi tick si ottiene esattamente la stessa partita.
+//
+// Formato del file (testo, una riga per voce):
+//   crablo-replay 1
+//   seed 1234
+//   mode rooms
+//   size 20 20
+//   end 900
+//   42 move 5 7
+//   60 attack 6 7
+//   75 stop
+//   97 stat strength
+//   120 item 0 0 3 1
+//   150 drop 3 1
+//   160 equip 0 0
+//   170 unequip weapon
+//   200 cast 1 9 4
+//   300 heal
+//   310 buy 2
+//   320 sell 0 0
+//   330 store 1 0
+//   340 retrieve 0 0
+//   350 close
+//   ...
+
+use crate::dungeon::{GenMode, MIN_SIZE};
+use crate::game::{Command, Game};
+use crate::item::ItemClass;
+use crate::player::Stat;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+// Intestazione e versione del formato
+const HEADER: &str = "crablo-replay 1";
+// Lato massimo della mappa accettato in un replay
+const MAX_SIZE: usize = 500;
+
+// Una partita registrata
+pub struct Replay {
+    pub seed: u64,
+    pub mode: GenMode,
+    pub size: (usize, usize),
+    // Comandi con il tick in cui sono stati eseguiti (in ordine)
+    pub commands: Vec<(u64, Command)>,
+    // Tick a cui si è fermata la registrazione
+    pub end: u64,
+}
+
+impl Replay {
+    // Nuova registrazione vuota per la partita indicata
+    pub fn new(game: &Game) -> Self {
+        Replay {
+            seed: game.seed,
+            mode: game.mode,
+            size: (game.map.width(), game.map.height()),
+            commands: vec![],
+            end: game.tick,
+        }
+    }
+
+    // Registra i comandi che stanno per essere passati a game.update()
+    pub fn record(&mut self, tick: u64, commands: &[Command]) {
+        self.commands.extend(commands.iter().map(|&c| (tick, c)));
+        self.end = tick + 1;
+    }
+
+    // Crea la partita iniziale da cui far partire la riproduzione
+    pub fn start(&self) -> Game {
+        Game::new(self.seed, self.mode, self.size)
+    }
+
+    // Converte il replay nel formato testuale
+    pub fn to_text(&self) -> String {
+        let mode = match self.mode {
+            GenMode::Rooms => "rooms",
+            GenMode::Caves => "caves",
+        };
+        let mut out = format!(
+            "{HEADER}\nseed {}\nmode {mode}\nsize {} {}\nend {}\n",
+            self.seed, self.size.0, self.size.1, self.end
+        );
+        for (tick, cmd) in &self.commands {
+            match cmd {
+                Command::MoveTo((x, y)) => out += &format!("{tick} move {x} {y}\n"),
+                Command::Attack((x, y)) => out += &format!("{tick} attack {x} {y}\n"),
+                Command::Stop => out += &format!("{tick} stop\n"),
+                Command::Heal => out += &format!("{tick} heal\n"),
+                Command::Buy(i) => out += &format!("{tick} buy {i}\n"),
+                Command::Sell((x, y)) => out += &format!("{tick} sell {x} {y}\n"),
+                Command::Store((x, y)) => out += &format!("{tick} store {x} {y}\n"),
+                Command::Retrieve((x, y)) => out += &format!("{tick} retrieve {x} {y}\n"),
+                Command::CloseDialog => out += &format!("{tick} close\n"),
+                Command::AddStat(stat) => out += &format!("{tick} stat {}\n", stat.name()),
+                Command::MoveItem { from, to } => {
+                    out += &format!("{tick} item {} {} {} {}\n", from.0, from.1, to.0, to.1)
+                }
+                Command::DropItem((x, y)) => out += &format!("{tick} drop {x} {y}\n"),
+                Command::Equip((x, y)) => out += &format!("{tick} equip {x} {y}\n"),
+                Command::Cast {
+                    skill,
+                    target: (x, y),
+                } => out += &format!("{tick} cast {skill} {x} {y}\n"),
+                Command::Unequip(class) => out += &format!("{tick} unequip {}\n", class.name()),
+            }
+        }
+        out
+    }
+
+    // Legge un replay dal formato testuale
+    pub fn from_text(text: &str) -> Result<Replay, Error> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, l)| l.trim()) != Some(HEADER) {
+            return Err(invalid(0, "missing header or unsupported version"));
+        }
+
+        let mut replay = Replay {
+            seed: 0,
+            mode: GenMode::Rooms,
+            size: (0, 0),
+            commands: vec![],
+            end: 0,
+        };
+        for (n, line) in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                ["seed", s] => replay.seed = num(n, s)?,
+                ["mode", "rooms"] => replay.mode = GenMode::Rooms,
+                ["mode", "caves"] => replay.mode = GenMode::Caves,
+                ["size", w, h] => {
+                    // Il file può arrivare da chiunque (es. una segnalazione di bug):
+                    // una mappa troppo piccola farebbe fallire la generazione,
+                    // una enorme allocherebbe memoria senza senso
+                    let size: (usize, usize) = (num(n, w)?, num(n, h)?);
+                    let range = MIN_SIZE..=MAX_SIZE;
+                    if !range.contains(&size.0) || !range.contains(&size.1) {
+                        let msg = format!("map size must be between {MIN_SIZE} and {MAX_SIZE}");
+                        return Err(invalid(n, &msg));
+                    }
+                    replay.size = size;
+                }
+                ["end", t] => replay.end = num(n, t)?,
+                [tick, "move", x, y] => replay
+                    .commands
+                    .push((num(n, tick)?, Command::MoveTo((num(n, x)?, num(n, y)?)))),
+                [tick, "attack", x, y] => replay
+                    .commands
+                    .push((num(n, tick)?, Command::Attack((num(n, x)?, num(n, y)?)))),
+                [tick, "stop"] => replay.commands.push((num(n, tick)?, Command::Stop)),
+                [tick, "heal"] => replay.commands.push((num(n, tick)?, Command::Heal)),
+                [tick, "buy", i] => replay
+                    .commands
+                    .push((num(n, tick)?, Command::Buy(num(n, i)?))),
+                [tick, "sell", x, y] => replay
+                    .commands
+                    .push((num(n, tick)?, Command::Sell((num(n, x)?, num(n, y)?)))),
+                [tick, "store", x, y] => replay
+                    .commands
+                    .push((num(n, tick)?, Command::Store((num(n, x)?, num(n, y)?)))),
+                [tick, "retrieve", x, y] => replay
+                    .commands
+                    .push((num(n, tick)?, Command::Retrieve((num(n, x)?, num(n, y)?)))),
+                [tick, "close"] => replay.commands.push((num(n, tick)?, Command::CloseDialog)),
+                [tick, "item", fx, fy, tx, ty] => replay.commands.push((
+                    num(n, tick)?,
+                    Command::MoveItem {
+                        from: (num(n, fx)?, num(n, fy)?),
+                        to: (num(n, tx)?, num(n, ty)?),
+                    },
+                )),
+                [tick, "drop", x, y] => replay
+                    .commands
+                    .push((num(n, tick)?, Command::DropItem((num(n, x)?, num(n, y)?)))),
+                [tick, "cast", skill, x, y] => replay.commands.push((
+                    num(n, tick)?,
+                    Command::Cast {
+                        skill: num(n, skill)?,
+                        target: (num(n, x)?, num(n, y)?),
+                    },
+                )),
+                [tick, "equip", x, y] => replay
+                    .commands
+                    .push((num(n, tick)?, Command::Equip((num(n, x)?, num(n, y)?)))),
+                [tick, "unequip", name] => {
+                    let class = ItemClass::ALL
+                        .into_iter()
+                        .find(|c| c.name() == *name)
+                        .ok_or_else(|| invalid(n, &format!("unknown slot '{name}'")))?;
+                    replay
+                        .commands
+                        .push((num(n, tick)?, Command::Unequip(class)));
+                }
+                [tick, "stat", name] => {
+                    let stat = Stat::ALL
+                        .into_iter()
+                        .find(|s| s.name() == *name)
+                        .ok_or_else(|| invalid(n, &format!("unknown stat '{name}'")))?;
+                    replay
+                        .commands
+                        .push((num(n, tick)?, Command::AddStat(stat)));
+                }
+                _ => return Err(invalid(n, "unrecognized line")),
+            }
+            // I comandi sono in ordine di tick (più comandi nello stesso tick sono
+            // normali): Playback li scorre in ordine e salterebbe quelli fuori posto
+            if let [.., (before, _), (tick, _)] = replay.commands.as_slice()
+                && tick < before
+            {
+                return Err(invalid(n, "command tick is earlier than the previous one"));
+            }
+        }
+        if replay.size == (0, 0) {
+            return Err(Error::new(ErrorKind::InvalidData, "replay has no map size"));
+        }
+        Ok(replay)
+    }
+
+    // Salva il replay su file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        std::fs::write(path, self.to_text())
+    }
+
+    // Carica un replay da file
+    pub fn load(path: impl AsRef<Path>) -> Result<Replay, Error> {
+        Replay::from_text(&std::fs::read_to_string(path)?)
+    }
+}
+
+// Errore di formato con il numero di riga (contando da 1)
+fn invalid(line: usize, msg: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("replay line {}: {msg}", line + 1),
+    )
+}
+
+// Legge un numero, con errore leggibile se la riga è corrotta
+fn num<T: std::str::FromStr>(line: usize, s: &str) -> Result<T, Error> {
+    s.parse()
+        .map_err(|_| invalid(line, &format!("invalid number '{s}'")))
+}
+
+// Riproduzione di un replay: passa alla partita i comandi registrati
+// esattamente al tick in cui erano stati dati
+pub struct Playback {
+    replay: Replay,
+    cursor: usize, // Indice del prossimo comando da eseguire
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Playback { replay, cursor: 0 }
+    }
+
+    // Avanza la partita di un tick. Ritorna true se la partita è finita
+    // (game over) come game.update()
+    pub fn step(&mut self, game: &mut Game) -> bool {
+        let start = self.cursor;
+        while self
+            .replay
+            .commands
+            .get(self.cursor)
+            .is_some_and(|(t, _)| *t == game.tick)
+        {
+            self.cursor += 1;
+        }
+        let commands: Vec<Command> = self.replay.commands[start..self.cursor]
+            .iter()
+            .map(|(_, c)| *c)
+            .collect();
+        game.update(&commands)
+    }
+
+    // true quando la partita ha raggiunto il punto in cui la registrazione si era fermata
+    pub fn finished(&self, game: &Game) -> bool {
+        game.tick >= self.replay.end
+    }
+
+    // Tick finale della registrazione (per mostrare l'avanzamento)
+    pub fn end(&self) -> u64 {
+        self.replay.end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_size(size: &str) -> String {
+        format!("{HEADER}\nseed 1\nmode rooms\n{size}\nend 10\n")
+    }
+
+    #[test]
+    fn rejects_bad_map_sizes() {
+        for size in ["size 3 3", "size 20 8", "size 100000 100000", ""] {
+            let err = Replay::from_text(&with_size(size)).err().expect(size);
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+        let replay = Replay::from_text(&with_size("size 20 20")).unwrap();
+        assert_eq!(replay.size, (20, 20));
+    }
+
+    #[test]
+    fn rejects_commands_out_of_tick_order() {
+        let text = with_size("size 20 20");
+        let replay = Replay::from_text(&format!("{text}3 stop\n3 heal\n5 stop\n")).unwrap();
+        assert_eq!(replay.commands.len(), 3);
+        let err = Replay::from_text(&format!("{text}5 stop\n3 heal\n"))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "replay line 7: command tick is earlier than the previous one"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+// Generatore di numeri pseudo-casuali con seed
+//
+// Usiamo un nostro generatore (PCG32, lo stesso algoritmo di macroquad::rand)
+// invece dello stato globale di macroquad: così ogni mappa dipende SOLO dal
+// seed che le passiamo, e lo stesso seed produce sempre lo stesso dungeon.
+
+use serde::{Deserialize, Serialize};
+
+const MULTIPLIER: u64 = 6364136223846793005;
+const INCREMENT: u64 = 1442695040888963407;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    // Crea un generatore a partire da un seed
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Rng { state: 0 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    // Ritorna un numero casuale tra 0 e u32::MAX
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    // Numero intero casuale nell'intervallo [lo, hi)
+    pub fn range(&mut self, lo: usize, hi: usize) -> usize {
+        if hi <= lo {
+            return lo;
+        }
+        lo + (self.next_u32() as usize) % (hi - lo)
+    }
+
+    // Ritorna true con probabilità p (tra 0.0 e 1.0)
+    pub fn chance(&mut self, p: f32) -> bool {
+        (self.next_u32() as f32 / u32::MAX as f32) < p
+    }
+}
@@ -0,0 +1,472 @@
+// Salvataggio e caricamento della partita
+//
+// Due formati per lo stesso contenuto (tutta la struct Game):
+// - RON (Rusty Object Notation): testo leggibile e modificabile a mano
+// - binario (bincode): molto più compatto, preceduto da un'intestazione "CRBL"
+//
+// Ogni salvataggio contiene un numero di versione: se la struttura di Game
+// cambia, si incrementa SAVE_VERSION e i file vecchi vengono rifiutati con un
+// errore chiaro invece di caricare dati senza senso.
+// Il formato viene riconosciuto automaticamente al caricamento.
+
+use crate::fov::Fog;
+use crate::game::{Game, Source, Target};
+use crate::item::Inventory;
+use crate::light::Torch;
+use crate::map::Map;
+use crate::town::Npc;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+// Versione attuale del formato di salvataggio
+pub const SAVE_VERSION: u32 = 15;
+// Intestazione dei file binari (per distinguerli dai file RON)
+const MAGIC: &[u8; 4] = b"CRBL";
+
+// Formato del file di salvataggio
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SaveFormat {
+    Ron,    // Testo leggibile
+    Binary, // Compatto
+}
+
+// Errori di salvataggio/caricamento
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),                     // File mancante, permessi, disco pieno...
+    Corrupted(String),                      // Contenuto non leggibile o incoerente
+    Outdated { found: u32, expected: u32 }, // Salvataggio di un'altra versione
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "{e}"),
+            SaveError::Corrupted(msg) => write!(f, "save file is corrupted ({msg})"),
+            SaveError::Outdated { found, expected } => write!(
+                f,
+                "save file version {found} is not supported (expected {expected})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+// Permette di usare l'operatore ? sulle operazioni di I/O
+impl From<std::io::Error> for SaveError {
+    fn from(e: std::io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+// Contenuto del file RON: versione + partita
+#[derive(Serialize)]
+struct SaveFile<'a> {
+    version: u32,
+    game: &'a Game,
+}
+
+// In lettura la versione è già stata controllata con VersionOnly
+#[derive(Deserialize)]
+struct LoadFile {
+    game: Game,
+}
+
+// Solo la versione: la leggiamo per prima, così un file di un'altra versione
+// dà l'errore "Outdated" invece di un generico errore di formato
+#[derive(Deserialize)]
+struct VersionOnly {
+    version: u32,
+}
+
+// Serializza la partita nel formato scelto
+pub fn to_bytes(game: &Game, format: SaveFormat) -> Result<Vec<u8>, SaveError> {
+    match format {
+        SaveFormat::Ron => {
+            let file = SaveFile {
+                version: SAVE_VERSION,
+                game,
+            };
+            ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default())
+                .map(String::into_bytes)
+                .map_err(|e| SaveError::Corrupted(e.to_string()))
+        }
+        SaveFormat::Binary => {
+            let mut bytes = MAGIC.to_vec();
+            bytes.extend(SAVE_VERSION.to_le_bytes());
+            bincode::serialize_into(&mut bytes, game)
+                .map_err(|e| SaveError::Corrupted(e.to_string()))?;
+            Ok(bytes)
+        }
+    }
+}
+
+// Ricostruisce la partita da un file salvato (formato riconosciuto dall'intestazione)
+pub fn from_bytes(bytes: &[u8]) -> Result<Game, SaveError> {
+    let mut game = if let Some(rest) = bytes.strip_prefix(MAGIC) {
+        // Binario: 4 byte di versione, poi la partita
+        let (version, data) = rest
+            .split_first_chunk::<4>()
+            .ok_or_else(|| SaveError::Corrupted("truncated header".into()))?;
+        check_version(u32::from_le_bytes(*version))?;
+        bincode::deserialize(data).map_err(|e| SaveError::Corrupted(e.to_string()))?
+    } else {
+        let text = std::str::from_utf8(bytes)
+            .map_err(|_| SaveError::Corrupted("not a RON or binary save".into()))?;
+        let header: VersionOnly =
+            ron::from_str(text).map_err(|e| SaveError::Corrupted(e.to_string()))?;
+        check_version(header.version)?;
+        let file: LoadFile =
+            ron::from_str(text).map_err(|e| SaveError::Corrupted(e.to_string()))?;
+        file.game
+    };
+
+    validate(&game)?;
+    game.restore();
+    Ok(game)
+}
+
+// Salva la partita su file
+pub fn save(game: &Game, path: impl AsRef<Path>, format: SaveFormat) -> Result<(), SaveError> {
+    std::fs::write(path, to_bytes(game, format)?)?;
+    Ok(())
+}
+
+// Carica la partita da file
+pub fn load(path: impl AsRef<Path>) -> Result<Game, SaveError> {
+    from_bytes(&std::fs::read(path)?)
+}
+
+fn check_version(found: u32) -> Result<(), SaveError> {
+    if found == SAVE_VERSION {
+        Ok(())
+    } else {
+        Err(SaveError::Outdated {
+            found,
+            expected: SAVE_VERSION,
+        })
+    }
+}
+
+// Controlli di coerenza: un file modificato a mano può essere sintatticamente
+// corretto ma con dati impossibili (che farebbero crashare il gioco)
+fn validate(game: &Game) -> Result<(), SaveError> {
+    let bad = |msg: &str| Err(SaveError::Corrupted(msg.into()));
+    if !game.map.is_consistent() {
+        return bad("map size does not match its tiles");
+    }
+    if !game.map.is_walkable(game.px, game.py) {
+        return bad("player is outside the map or inside a wall");
+    }
+    if game.monsters.iter().any(|m| !game.map.in_bounds(m.x, m.y)) {
+        return bad("monster outside the map");
+    }
+    if game.monsters.iter().any(|m| m.kind >= game.kinds.len()) {
+        return bad("unknown monster kind");
+    }
+    if game.ground.iter().any(|g| !game.map.in_bounds(g.x, g.y)) {
+        return bad("item outside the map");
+    }
+    // Piani già visitati: stessi controlli di quello attuale
+    for floor in game.floors.values() {
+        if !floor.map.is_consistent() {
+            return bad("map size does not match its tiles");
+        }
+        if floor
+            .monsters
+            .iter()
+            .any(|m| !floor.map.in_bounds(m.x, m.y) || m.kind >= game.kinds.len())
+        {
+            return bad("monster outside the map");
+        }
+        if floor.ground.iter().any(|g| !floor.map.in_bounds(g.x, g.y)) {
+            return bad("item outside the map");
+        }
+    }
+    let npcs_ok = |npcs: &[Npc], map: &Map| npcs.iter().all(|n| map.in_bounds(n.x, n.y));
+    if !npcs_ok(&game.npcs, &game.map) || game.floors.values().any(|f| !npcs_ok(&f.npcs, &f.map)) {
+        return bad("npc outside the map");
+    }
+    if game.dialog.is_some_and(|i| i >= game.npcs.len()) {
+        return bad("dialog with a missing npc");
+    }
+    if let Some(Target::Npc(i)) = game.target
+        && i >= game.npcs.len()
+    {
+        return bad("target is not an npc");
+    }
+    // La memoria di cosa è stato esplorato deve coprire esattamente la mappa
+    let fog_ok = |fog: &Fog, map: &Map| {
+        fog.tiles.is_consistent()
+            && fog.tiles.width() == map.width()
+            && fog.tiles.height() == map.height()
+    };
+    if !fog_ok(&game.fog, &game.map) || game.floors.values().any(|f| !fog_ok(&f.fog, &f.map)) {
+        return bad("fog size does not match the map");
+    }
+    // Le torce stanno sulla mappa, e la luce parte da una cella della mappa
+    let torches_ok = |torches: &[Torch], map: &Map| {
+        torches.iter().all(|t| {
+            let (x, y) = t.origin();
+            map.in_bounds(t.x, t.y) && map.in_bounds(x, y)
+        })
+    };
+    if !torches_ok(&game.torches, &game.map)
+        || game
+            .floors
+            .values()
+            .any(|f| !torches_ok(&f.torches, &f.map))
+    {
+        return bad("torch outside the map");
+    }
+    if game.floors.contains_key(&game.depth) {
+        return bad("current floor is also stored as visited");
+    }
+    // Tutti gli oggetti della partita: a terra (anche negli altri piani),
+    // nell'inventario, indossati, nel forziere e in vendita dai mercanti
+    let floors = game.floors.values();
+    let items = game.ground.iter().map(|g| &g.item);
+    if items
+        .chain(
+            floors
+                .clone()
+                .flat_map(|f| f.ground.iter().map(|g| &g.item)),
+        )
+        .chain(game.inventory.items.iter().map(|s| &s.item))
+        .chain(game.equipment.slots.iter().flatten())
+        .chain(game.stash.items.iter().map(|s| &s.item))
+        .chain(game.npcs.iter().flat_map(|n| &n.stock))
+        .chain(floors.flat_map(|f| f.npcs.iter().flat_map(|n| &n.stock)))
+        .any(|i| i.base >= game.item_data.bases.len())
+    {
+        return bad("unknown item");
+    }
+    if game.skill_cd.len() != game.skills.len() {
+        return bad("skill cooldowns do not match the skills");
+    }
+    // Proiettili ed esplosioni indicano chi li ha prodotti: il tipo di mostro
+    // o l'abilità devono esistere (servono per il colore e per chi colpiscono)
+    let source_ok = |s: &Source| match *s {
+        Source::Monster(k) => k < game.kinds.len(),
+        Source::Player(s) => s < game.skills.len(),
+    };
+    if game.projectiles.iter().any(|p| !source_ok(&p.source)) {
+        return bad("projectile from an unknown source");
+    }
+    if game.blasts.iter().any(|b| b.skill >= game.skills.len()) {
+        return bad("blast from an unknown skill");
+    }
+    let grid_ok = |inv: &Inventory| {
+        (0..inv.items.len()).all(|i| inv.fits(inv.items[i].pos, inv.items[i].size, Some(i)))
+    };
+    if !grid_ok(&game.inventory) {
+        return bad("inventory items overlap or do not fit");
+    }
+    if !grid_ok(&game.stash) {
+        return bad("stash items overlap or do not fit");
+    }
+    if let Some(Target::Monster(i)) = game.target
+        && i >= game.monsters.len()
+    {
+        return bad("target is not a monster");
+    }
+    if game.path.iter().any(|&(x, y)| !game.map.in_bounds(x, y)) {
+        return bad("path outside the map");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::damage::DamageType;
+    use crate::dungeon::GenMode;
+    use crate::game::{Blast, Command, Projectile};
+    use crate::item::{Item, Rarity, Stored};
+    use crate::rng::Rng;
+
+    fn game() -> Game {
+        Game::new(3, GenMode::Rooms, (20, 20))
+    }
+
+    // Oggetto con una base che non esiste nei dati
+    fn unknown_item() -> Item {
+        Item {
+            base: usize::MAX,
+            rarity: Rarity::Normal,
+            name: "???".into(),
+            mods: vec![],
+        }
+    }
+
+    // Messaggio dell'errore di validazione (None se la partita è valida)
+    fn error(g: &Game) -> Option<String> {
+        match validate(g) {
+            Err(SaveError::Corrupted(msg)) => Some(msg),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_items_in_the_stash_and_shops() {
+        assert_eq!(error(&game()), None);
+        let mut g = game();
+        g.stash.insert(unknown_item(), (1, 1)).unwrap();
+        assert_eq!(error(&g).as_deref(), Some("unknown item"));
+
+        let mut g = game();
+        let (_, mut npcs, _) = crate::town::build(20, 20, &g.item_data, &mut Rng::new(1));
+        npcs[0].stock.push(unknown_item());
+        g.npcs = npcs;
+        assert_eq!(error(&g).as_deref(), Some("unknown item"));
+    }
+
+    // Comandi di prova ai vari tick, scelti tra le celle in vista all'inizio
+    fn script(g: &Game) -> Vec<(u64, Command)> {
+        let cells: Vec<_> = g
+            .map
+            .iter()
+            .filter(|&(c, t)| t.walkable() && g.fog.visible(c) && c != (g.px, g.py))
+            .map(|(c, _)| c)
+            .collect();
+        let n = cells.len();
+        vec![
+            (1, Command::MoveTo(cells[n - 1])),
+            (
+                120,
+                Command::Cast {
+                    skill: 0,
+                    target: cells[0],
+                },
+            ),
+            (200, Command::Attack(cells[n / 3])),
+            (260, Command::Stop),
+            (320, Command::MoveTo(cells[n / 2])),
+            (
+                400,
+                Command::Cast {
+                    skill: 1,
+                    target: cells[n / 4],
+                },
+            ),
+            (480, Command::MoveTo(cells[0])),
+        ]
+    }
+
+    // Avanza la partita fino al tick `until` dando i comandi del copione
+    fn play(g: &mut Game, script: &[(u64, Command)], until: u64) {
+        while g.tick < until {
+            let commands: Vec<_> = script
+                .iter()
+                .filter(|&&(t, _)| t == g.tick + 1)
+                .map(|&(_, c)| c)
+                .collect();
+            g.update(&commands);
+        }
+    }
+
+    fn snapshot(g: &Game) -> Vec<u8> {
+        to_bytes(g, SaveFormat::Ron).unwrap()
+    }
+
+    #[test]
+    fn same_seed_and_commands_give_the_same_game() {
+        for seed in 1..=3 {
+            let mut a = Game::new(seed, GenMode::Rooms, (20, 20));
+            let mut b = Game::new(seed, GenMode::Rooms, (20, 20));
+            let commands = script(&a);
+            play(&mut a, &commands, 600);
+            play(&mut b, &commands, 600);
+            assert!(snapshot(&a) == snapshot(&b), "seed {seed}");
+
+            // A metà partita si salva e si ricarica (in entrambi i formati):
+            // la partita continua esattamente come senza salvataggio
+            for format in [SaveFormat::Ron, SaveFormat::Binary] {
+                let mut c = Game::new(seed, GenMode::Rooms, (20, 20));
+                play(&mut c, &commands, 300);
+                let mut c = from_bytes(&to_bytes(&c, format).unwrap()).unwrap();
+                play(&mut c, &commands, 600);
+                assert!(snapshot(&a) == snapshot(&c), "seed {seed} {format:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_overlapping_stash_items() {
+        let mut g = game();
+        let item = g.item_data.generate(0, &mut Rng::new(1));
+        for _ in 0..2 {
+            g.stash.items.push(Stored {
+                pos: (0, 0),
+                size: (1, 1),
+                item: item.clone(),
+            });
+        }
+        assert_eq!(
+            error(&g).as_deref(),
+            Some("stash items overlap or do not fit")
+        );
+    }
+
+    // Salva e ricarica la partita, e ritorna il messaggio dell'errore
+    fn load_error(g: &Game) -> Option<String> {
+        let bytes = to_bytes(g, SaveFormat::Binary).unwrap();
+        match from_bytes(&bytes) {
+            Err(SaveError::Corrupted(msg)) => Some(msg),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn rejects_projectiles_and_blasts_from_unknown_sources() {
+        let projectile = |source| Projectile {
+            pos: (5., 5.),
+            dir: (1., 0.),
+            speed: 5.,
+            range: 3.,
+            damage: 1,
+            radius: 0.,
+            source,
+            status: None,
+            damage_type: DamageType::Physical,
+        };
+        let mut g = game();
+        g.projectiles.push(projectile(Source::Monster(0)));
+        g.projectiles.push(projectile(Source::Player(0)));
+        g.blasts.push(Blast {
+            pos: (5., 5.),
+            radius: 1.,
+            skill: 0,
+            life: 0.2,
+        });
+        assert_eq!(load_error(&g), None);
+
+        let mut g = game();
+        g.projectiles
+            .push(projectile(Source::Monster(g.kinds.len())));
+        assert_eq!(
+            load_error(&g).as_deref(),
+            Some("projectile from an unknown source")
+        );
+        let mut g = game();
+        g.projectiles
+            .push(projectile(Source::Player(g.skills.len())));
+        assert_eq!(
+            load_error(&g).as_deref(),
+            Some("projectile from an unknown source")
+        );
+        let mut g = game();
+        g.blasts.push(Blast {
+            pos: (5., 5.),
+            radius: 1.,
+            skill: g.skills.len(),
+            life: 0.2,
+        });
+        assert_eq!(
+            load_error(&g).as_deref(),
+            Some("blast from an unknown skill")
+        );
+    }
+}
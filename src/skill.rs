@@ -0,0 +1,85 @@
+// Abilità attive del player (skill)
+//
+// Ogni abilità è descritta in data/skills.ron come una combinazione di effetti
+// già previsti dal gioco (danno ad area, proiettile, teletrasporto), con il suo
+// costo in mana, il tempo di ricarica e la portata. Per aggiungere un'abilità
+// basta aggiungere una voce al file: Game::update non cambia.
+// L'ordine nel file è anche l'ordine nella barra delle abilità (tasti 1-8).
+
+use crate::damage::DamageType;
+use crate::status::Inflict;
+use serde::{Deserialize, Serialize};
+
+// Contenuto di data/skills.ron
+const SKILLS_RON: &str = include_str!("../data/skills.ron");
+
+// Numero di caselle della barra delle abilità
+pub const HOTBAR_SIZE: usize = 8;
+
+// Danno di un effetto: una frazione del danno dell'arma più un intervallo fisso
+#[derive(Copy, Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SkillDamage {
+    #[serde(default)]
+    pub weapon: f32, // Moltiplicatore del danno del player (1.0 = un colpo normale)
+    #[serde(default)]
+    pub flat: (i32, i32), // Danno aggiunto (minimo, massimo)
+    #[serde(default)]
+    pub status: Option<Inflict>, // Effetto di stato applicato ai mostri colpiti
+    #[serde(default)]
+    pub kind: DamageType, // Tipo di danno (fisico se non indicato)
+}
+
+// Punto da cui parte un effetto ad area
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Anchor {
+    Caster, // Intorno al player
+    Target, // Intorno alla cella bersaglio
+}
+
+// Effetti che un'abilità può combinare
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Effect {
+    // Colpisce tutti i mostri entro `radius` celle dal punto indicato
+    Area {
+        at: Anchor,
+        radius: f32,
+        damage: SkillDamage,
+    },
+    // Lancia un proiettile verso il bersaglio; al primo mostro colpito
+    // esplode danneggiando tutti quelli entro `radius` celle
+    Projectile {
+        speed: f32,
+        radius: f32,
+        damage: SkillDamage,
+    },
+    // Sposta il player sulla cella bersaglio (se è libera)
+    Teleport,
+}
+
+// Definizione di un'abilità
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SkillDef {
+    pub name: String,
+    pub mana: i32,              // Costo in mana
+    pub cooldown: f32,          // Secondi prima di poterla riusare
+    pub range: f32,             // Distanza massima del bersaglio in celle (0 = senza bersaglio)
+    pub color: (f32, f32, f32), // Colore RGB (0..1) di proiettili ed esplosioni
+    pub effects: Vec<Effect>,
+}
+
+// Legge le abilità in formato RON
+pub fn parse_skills(text: &str) -> Result<Vec<SkillDef>, String> {
+    let skills: Vec<SkillDef> = ron::from_str(text).map_err(|e| e.to_string())?;
+    if skills.len() > HOTBAR_SIZE {
+        return Err(format!("at most {HOTBAR_SIZE} skills fit in the hotbar"));
+    }
+    if let Some(s) = skills.iter().find(|s| s.mana < 0 || s.cooldown < 0.) {
+        return Err(format!("{}: mana and cooldown cannot be negative", s.name));
+    }
+    Ok(skills)
+}
+
+// Abilità standard (data/skills.ron)
+pub fn default_skills() -> Vec<SkillDef> {
+    parse_skills(SKILLS_RON).unwrap_or_else(|e| panic!("data/skills.ron: {e}"))
+}
@@ -0,0 +1,223 @@
+// Effetti di stato (veleno, fuoco, rallentamento, stordimento)
+//
+// Valgono sia per il player sia per i mostri: ognuno ha la sua lista di
+// effetti attivi, che Game::update fa scorrere a ogni tick.
+// - veleno e fuoco infliggono danno nel tempo (ignorano la difesa)
+// - il rallentamento allunga i tempi di passi e attacchi
+// - lo stordimento impedisce qualsiasi azione
+// Regole di accumulo: più veleni si sommano (ognuno con la sua durata), mentre
+// un nuovo fuoco, rallentamento o stordimento rinnova quello già attivo
+// tenendo il più forte e il più lungo.
+
+use crate::damage::{DamageType, Resists};
+use serde::{Deserialize, Serialize};
+
+// Tipi di effetto
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum StatusKind {
+    Poison, // power = danno al secondo, si accumula
+    Burn,   // power = danno al secondo, non si accumula
+    Slow,   // power = percentuale di tempo in più per passi e attacchi
+    Stun,   // power ignorato: nessuna azione finché dura
+}
+
+impl StatusKind {
+    pub const ALL: [StatusKind; 4] = [
+        StatusKind::Poison,
+        StatusKind::Burn,
+        StatusKind::Slow,
+        StatusKind::Stun,
+    ];
+
+    // Nome mostrato nell'HUD
+    pub fn name(self) -> &'static str {
+        match self {
+            StatusKind::Poison => "poison",
+            StatusKind::Burn => "burn",
+            StatusKind::Slow => "slow",
+            StatusKind::Stun => "stun",
+        }
+    }
+
+    // Tipo del danno nel tempo (None per gli effetti che non fanno danno)
+    pub fn damage_type(self) -> Option<DamageType> {
+        match self {
+            StatusKind::Poison => Some(DamageType::Poison),
+            StatusKind::Burn => Some(DamageType::Fire),
+            StatusKind::Slow | StatusKind::Stun => None,
+        }
+    }
+}
+
+// Effetto da applicare a chi viene colpito (nei file dei dati: abilità e mostri)
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Inflict {
+    pub kind: StatusKind,
+    #[serde(default)]
+    pub power: i32,
+    pub time: f32, // Durata in secondi
+}
+
+impl Inflict {
+    // Effetto ridotto dalle resistenze di chi lo subisce:
+    // il danno al secondo di veleno e fuoco cala come un colpo normale
+    pub fn resisted(self, resists: &Resists) -> Inflict {
+        match self.kind.damage_type() {
+            Some(t) => Inflict {
+                power: resists.reduce(self.power, t),
+                ..self
+            },
+            None => self,
+        }
+    }
+}
+
+// Un effetto attivo
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Status {
+    pub kind: StatusKind,
+    pub power: i32,
+    pub time: f32, // Secondi rimanenti
+}
+
+// Effetti attivi su un personaggio
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct Effects {
+    pub list: Vec<Status>,
+    // Danno nel tempo non ancora arrivato a un punto intero
+    dot: f32,
+}
+
+impl Effects {
+    // Applica un effetto seguendo le regole di accumulo
+    pub fn apply(&mut self, inflict: Inflict) {
+        if inflict.kind != StatusKind::Poison
+            && let Some(s) = self.list.iter_mut().find(|s| s.kind == inflict.kind)
+        {
+            s.power = s.power.max(inflict.power);
+            s.time = s.time.max(inflict.time);
+            return;
+        }
+        self.list.push(Status {
+            kind: inflict.kind,
+            power: inflict.power,
+            time: inflict.time,
+        });
+    }
+
+    // Fa passare dt secondi: toglie gli effetti scaduti e ritorna il danno
+    // nel tempo da infliggere in questo tick (spesso 0)
+    pub fn update(&mut self, dt: f32) -> i32 {
+        for s in &self.list {
+            if matches!(s.kind, StatusKind::Poison | StatusKind::Burn) {
+                self.dot += s.power as f32 * dt.min(s.time);
+            }
+        }
+        self.list.retain_mut(|s| {
+            s.time -= dt;
+            s.time > 0.
+        });
+        let damage = self.dot.floor();
+        self.dot -= damage;
+        damage as i32
+    }
+
+    // Vero se c'è almeno un effetto di questo tipo
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.list.iter().any(|s| s.kind == kind)
+    }
+
+    // Vero se lo stordimento impedisce di agire
+    pub fn stunned(&self) -> bool {
+        self.has(StatusKind::Stun)
+    }
+
+    // Moltiplicatore dei tempi di passi e attacchi (1 = normale)
+    pub fn cooldown(&self) -> f32 {
+        let slow = self
+            .list
+            .iter()
+            .filter(|s| s.kind == StatusKind::Slow)
+            .map(|s| s.power)
+            .max()
+            .unwrap_or(0);
+        1. + slow as f32 / 100.
+    }
+
+    // Secondi rimanenti dell'effetto più lungo di questo tipo (0 se assente)
+    pub fn remaining(&self, kind: StatusKind) -> f32 {
+        self.list
+            .iter()
+            .filter(|s| s.kind == kind)
+            .map(|s| s.time)
+            .fold(0., f32::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inflict(kind: StatusKind, power: i32, time: f32) -> Inflict {
+        Inflict { kind, power, time }
+    }
+
+    #[test]
+    fn poison_stacks_and_the_others_refresh() {
+        let mut e = Effects::default();
+        e.apply(inflict(StatusKind::Poison, 2, 3.));
+        e.apply(inflict(StatusKind::Poison, 1, 1.));
+        assert_eq!(e.list.len(), 2);
+
+        // Fuoco, rallentamento e stordimento: resta un solo effetto, con la
+        // potenza e la durata più alte tra i due
+        for kind in [StatusKind::Burn, StatusKind::Slow, StatusKind::Stun] {
+            e.apply(inflict(kind, 5, 1.));
+            e.apply(inflict(kind, 3, 4.));
+            let found: Vec<_> = e.list.iter().filter(|s| s.kind == kind).collect();
+            assert_eq!(found.len(), 1);
+            assert_eq!((found[0].power, found[0].time), (5, 4.));
+        }
+        assert_eq!(e.remaining(StatusKind::Poison), 3.);
+        assert!(e.stunned());
+        assert_eq!(e.cooldown(), 1.05);
+    }
+
+    #[test]
+    fn damage_over_time_ticks_and_expires() {
+        let mut e = Effects::default();
+        e.apply(inflict(StatusKind::Poison, 3, 2.));
+        e.apply(inflict(StatusKind::Slow, 50, 1.));
+        // 1.5 danni ogni mezzo secondo: la parte decimale si accumula
+        let ticks: Vec<_> = (0..4).map(|_| e.update(0.5)).collect();
+        assert_eq!(ticks, [1, 2, 1, 2]);
+        assert!(e.list.is_empty());
+        assert_eq!(e.cooldown(), 1.);
+
+        // Un effetto che scade a metà del tick fa danno solo per il tempo rimasto
+        let mut e = Effects::default();
+        e.apply(inflict(StatusKind::Burn, 4, 0.5));
+        e.apply(inflict(StatusKind::Poison, 2, 1.));
+        assert_eq!(e.update(1.), 4);
+        assert!(!e.has(StatusKind::Burn));
+        assert_eq!(e.update(1.), 0);
+    }
+
+    #[test]
+    fn resists_reduce_only_damage_over_time() {
+        let resists = Resists {
+            fire: 50,
+            poison: 100,
+            ..Resists::default()
+        };
+        let burn = inflict(StatusKind::Burn, 10, 2.).resisted(&resists);
+        assert_eq!((burn.power, burn.time), (5, 2.));
+        // Resistenza oltre il massimo: resta comunque un quarto del danno
+        assert_eq!(
+            inflict(StatusKind::Poison, 8, 2.).resisted(&resists).power,
+            2
+        );
+        let slow = inflict(StatusKind::Slow, 30, 2.);
+        assert_eq!(slow.resisted(&resists), slow);
+    }
+}
@@ -0,0 +1,168 @@
+// Città: il piano 0, sopra il primo piano del dungeon
+//
+// È un luogo sicuro (nessun mostro) con una mappa fatta a mano invece che
+// generata, le scale per scendere nel dungeon e tre personaggi non giocanti:
+// - il guaritore, che ripristina vita e mana
+// - il mercante, che vende e compra oggetti in cambio di oro
+// - il forziere, dove lasciare gli oggetti che non si vogliono portare dietro
+// Il player parla con un NPC cliccandolo: lo raggiunge e si apre il dialogo.
+
+use crate::dungeon::MIN_SIZE;
+use crate::item::{Item, ItemData};
+use crate::light::{self, Torch, TorchKind};
+use crate::map::{Map, Tile};
+use crate::rng::Rng;
+use serde::{Deserialize, Serialize};
+
+// Oggetti in vendita dal mercante alla creazione della città
+const STOCK_SIZE: usize = 6;
+
+// Oggetti che il mercante tiene al massimo: vendendogliene altri, i più
+// vecchi spariscono
+pub const MAX_STOCK: usize = 10;
+
+// Ruolo di un NPC (decide dialogo e aspetto)
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Role {
+    Healer,
+    Merchant,
+    Stash,
+}
+
+impl Role {
+    // Nome mostrato sopra l'NPC e nel dialogo
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Healer => "Healer",
+            Role::Merchant => "Merchant",
+            Role::Stash => "Stash",
+        }
+    }
+}
+
+// Personaggio non giocante: sta fermo nella sua cella e non combatte
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Npc {
+    pub x: usize,
+    pub y: usize,
+    pub role: Role,
+    // Oggetti in vendita (solo per il mercante)
+    pub stock: Vec<Item>,
+}
+
+// Costruisce la città su una mappa w x h (almeno dungeon::MIN_SIZE per lato):
+// una piazza circondata da muri, con una fontana al centro tra quattro bracieri,
+// le scale per il dungeon sotto la fontana e gli NPC attorno. Le posizioni si
+// calcolano dal centro, così la piazza sta in qualsiasi mappa accettata dal
+// gioco; le due case negli angoli in alto si costruiscono solo se c'è posto.
+// Ritorna mappa, NPC e torce
+pub fn build(w: usize, h: usize, data: &ItemData, rng: &mut Rng) -> (Map, Vec<Npc>, Vec<Torch>) {
+    assert!(
+        w >= MIN_SIZE && h >= MIN_SIZE,
+        "città troppo piccola: {w}x{h} (minimo {MIN_SIZE}x{MIN_SIZE})"
+    );
+    let mut map = Map::new(w, h, Tile::Wall);
+    for y in 1..h - 1 {
+        for x in 1..w - 1 {
+            map[(x, y)] = Tile::Floor;
+        }
+    }
+    // Piazza: fontana 2x2 in (cx - 1..=cx, cy - 1..=cy), forziere sopra,
+    // guaritore e mercante ai lati, scale sotto. Occupa 3 celle in ogni
+    // direzione dal centro: con MIN_SIZE = 9 arriva giusto al bordo
+    let (cx, cy) = (w / 2, h / 2);
+    for y in cy - 1..=cy {
+        for x in cx - 1..=cx {
+            map[(x, y)] = Tile::Water;
+        }
+    }
+    map[(cx, cy + 3)] = Tile::StairsDown;
+    // Case: blocchi di muro negli angoli in alto, ad almeno una cella dalla
+    // piazza (sulle mappe piccole non ci stanno)
+    let square = (cx - 3, cx + 3, cy - 3, cy + 3);
+    for x0 in [2, w - 5] {
+        let (x1, y0, y1) = (x0 + 2, 2, 3);
+        let apart = x1 + 1 < square.0 || square.1 + 1 < x0 || y1 + 1 < square.2;
+        if apart {
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    map[(x, y)] = Tile::Wall;
+                }
+            }
+        }
+    }
+
+    // Il mercante vende oggetti scelti a caso tra tutti i tipi base
+    let stock = (0..STOCK_SIZE)
+        .map(|_| data.generate(rng.range(0, data.bases.len()), rng))
+        .collect();
+    let npcs = vec![
+        Npc {
+            x: cx - 3,
+            y: cy + 1,
+            role: Role::Healer,
+            stock: vec![],
+        },
+        Npc {
+            x: cx + 3,
+            y: cy + 1,
+            role: Role::Merchant,
+            stock,
+        },
+        Npc {
+            x: cx,
+            y: cy - 3,
+            role: Role::Stash,
+            stock: vec![],
+        },
+    ];
+    // Bracieri agli angoli della fontana, più qualche torcia sui muri
+    let mut torches = light::place_torches(&map, rng, light::torch_count(w, h));
+    for (x, y) in [
+        (cx - 2, cy - 2),
+        (cx + 1, cy - 2),
+        (cx - 2, cy + 1),
+        (cx + 1, cy + 1),
+    ] {
+        torches.push(Torch {
+            x,
+            y,
+            kind: TorchKind::Brazier,
+        });
+    }
+    (map, npcs, torches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item;
+    use crate::path::bfs;
+
+    #[test]
+    fn npcs_stand_on_reachable_floor_at_every_size() {
+        let data = item::default_items();
+        let mut sizes = vec![];
+        for w in MIN_SIZE..=40 {
+            for h in MIN_SIZE..=40 {
+                sizes.push((w, h));
+            }
+        }
+        sizes.extend([(500, MIN_SIZE), (MIN_SIZE, 500), (500, 500)]);
+        for (w, h) in sizes {
+            let (map, npcs, torches) = build(w, h, &data, &mut Rng::new(1));
+            let stairs = map
+                .iter()
+                .find(|&(_, &t)| t == Tile::StairsDown)
+                .map(|(c, _)| c)
+                .expect("città senza scale");
+            for (i, n) in npcs.iter().enumerate() {
+                let cell = (n.x, n.y);
+                assert_eq!(map[cell], Tile::Floor, "{w}x{h} {:?}", n.role);
+                assert!(!bfs(&map, stairs, cell).is_empty(), "{w}x{h} {:?}", n.role);
+                assert!(npcs[..i].iter().all(|o| (o.x, o.y) != cell));
+            }
+            assert!(torches.iter().all(|t| map.in_bounds(t.x, t.y)));
+        }
+    }
+}